@@ -12,6 +12,7 @@ fn main() {
         .warnings(true)
         .warnings_into_errors(true)
         .compile("mute_control");
+    println!("cargo:rustc-link-lib=runtimeobject");
 
     // embed manifest + icon
 
@@ -0,0 +1,159 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use log::info;
+use winapi::shared::minwindef::{BOOL, DWORD, FALSE, TRUE, UINT};
+
+use crate::{
+    core::{CoreMessage, CoreSender},
+    process::ProcessId,
+};
+
+extern "C" {
+    fn GetAudioSessionActivity(
+        bCapture: BOOL,
+        pPIDs: *mut DWORD,
+        pActive: *mut BOOL,
+        pPeaks: *mut f32,
+        cMaxSessions: UINT,
+    ) -> UINT;
+}
+
+const POLL_INTERVAL_MS: u64 = 500;
+const MAX_SESSIONS: usize = 256;
+
+// peak level above which a session counts as producing sound
+const PEAK_THRESHOLD: f32 = 0.001;
+
+// short pauses (e.g. between songs or game sounds) don't count as stopping
+const SILENCE_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Polls the audio sessions and reports to the core whenever a process starts or stops
+/// producing sound or recording from the microphone.
+pub struct AudioMonitorHandle {
+    stop_flag: Arc<AtomicBool>,
+    monitor_thread: JoinHandle<()>,
+}
+
+impl AudioMonitorHandle {
+    pub fn spawn(core_sender: CoreSender) -> Self {
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let stop_flag_clone = stop_flag.clone();
+        let monitor_thread = thread::spawn(move || run_monitor(core_sender, stop_flag_clone));
+
+        AudioMonitorHandle {
+            stop_flag,
+            monitor_thread,
+        }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.stop_flag.store(true, Ordering::SeqCst);
+        self.monitor_thread.join()
+    }
+}
+
+fn run_monitor(core_sender: CoreSender, stop_flag: Arc<AtomicBool>) {
+    info!("Audio monitor start");
+
+    let mut playing_processes = HashSet::new();
+    let mut capturing_processes = HashSet::new();
+    let mut last_heard = HashMap::<ProcessId, Instant>::new();
+
+    while !stop_flag.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        for process in get_playing_processes() {
+            last_heard.insert(process, now);
+        }
+        last_heard.retain(|_, heard| now.duration_since(*heard) < SILENCE_GRACE_PERIOD);
+
+        let playing_processes_new: HashSet<ProcessId> = last_heard.keys().copied().collect();
+
+        let started = playing_processes_new
+            .difference(&playing_processes)
+            .map(|&process| CoreMessage::AudioActivityChanged(process, true));
+        let stopped = playing_processes
+            .difference(&playing_processes_new)
+            .map(|&process| CoreMessage::AudioActivityChanged(process, false));
+
+        let capturing_processes_new = get_capturing_processes();
+
+        let capture_started = capturing_processes_new
+            .difference(&capturing_processes)
+            .map(|&process| CoreMessage::CaptureActivityChanged(process, true));
+        let capture_stopped = capturing_processes
+            .difference(&capturing_processes_new)
+            .map(|&process| CoreMessage::CaptureActivityChanged(process, false));
+
+        let messages = started
+            .chain(stopped)
+            .chain(capture_started)
+            .chain(capture_stopped);
+
+        for message in messages {
+            if core_sender.send(message).is_err() {
+                info!("Audio monitor exit");
+                return;
+            }
+        }
+
+        playing_processes = playing_processes_new;
+        capturing_processes = capturing_processes_new;
+        thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+    }
+
+    info!("Audio monitor exit");
+}
+
+/// Processes with an active audio session that is currently producing sound.
+fn get_playing_processes() -> HashSet<ProcessId> {
+    let mut pids = [0 as DWORD; MAX_SESSIONS];
+    let mut active = [FALSE; MAX_SESSIONS];
+    let mut peaks = [0f32; MAX_SESSIONS];
+
+    let session_count = unsafe {
+        GetAudioSessionActivity(
+            FALSE,
+            pids.as_mut_ptr(),
+            active.as_mut_ptr(),
+            peaks.as_mut_ptr(),
+            MAX_SESSIONS as _,
+        )
+    } as usize;
+
+    // pid 0 is the system sounds session
+    (0..session_count)
+        .filter(|&i| pids[i] != 0 && active[i] != FALSE && peaks[i] > PEAK_THRESHOLD)
+        .filter_map(|i| ProcessId::from_pid(pids[i]))
+        .collect()
+}
+
+/// Processes with an active capture session on the default recording device.
+fn get_capturing_processes() -> HashSet<ProcessId> {
+    let mut pids = [0 as DWORD; MAX_SESSIONS];
+    let mut active = [FALSE; MAX_SESSIONS];
+    let mut peaks = [0f32; MAX_SESSIONS];
+
+    let session_count = unsafe {
+        GetAudioSessionActivity(
+            TRUE,
+            pids.as_mut_ptr(),
+            active.as_mut_ptr(),
+            peaks.as_mut_ptr(),
+            MAX_SESSIONS as _,
+        )
+    } as usize;
+
+    // an open microphone stream counts regardless of the input level
+    (0..session_count)
+        .filter(|&i| pids[i] != 0 && active[i] != FALSE)
+        .filter_map(|i| ProcessId::from_pid(pids[i]))
+        .collect()
+}
@@ -1,4 +1,9 @@
-use std::{collections::HashSet, fmt::Write, fs, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Write},
+    fs,
+    path::Path,
+};
 
 use flexstr::SharedStr;
 use itertools::Itertools;
@@ -11,9 +16,188 @@ use crate::core::ProgramPath;
 #[derive(Clone, Deserialize, Debug)]
 pub struct AnnieConfig {
     pub enabled: bool,
-    #[serde(deserialize_with = "deserialize_managed_apps")]
+    #[serde(deserialize_with = "deserialize_program_paths")]
     pub managed_apps: HashSet<ProgramPath>,
     pub max_recent_apps: usize,
+    #[serde(default = "default_audible_recent_apps")]
+    pub audible_recent_apps: usize,
+    #[serde(default = "default_duck_volume")]
+    pub duck_volume: f64,
+    #[serde(default)]
+    pub reroute_output_device: String,
+    #[serde(default, deserialize_with = "deserialize_program_paths")]
+    pub reroute_apps: HashSet<ProgramPath>,
+    #[serde(default)]
+    pub keep_visible_apps_audible: bool,
+    #[serde(default)]
+    pub keep_topmost_apps_audible: bool,
+    #[serde(default)]
+    pub keep_current_desktop_apps_audible: bool,
+    #[serde(default, deserialize_with = "deserialize_program_paths")]
+    pub minimize_only_apps: HashSet<ProgramPath>,
+    #[serde(default, deserialize_with = "deserialize_program_paths")]
+    pub mute_capture_apps: HashSet<ProgramPath>,
+    #[serde(default)]
+    pub mute_when_idle_secs: u64,
+    #[serde(default)]
+    pub mute_when_locked: bool,
+    #[serde(default)]
+    pub mute_all_apps_when_away: bool,
+    #[serde(default)]
+    pub fullscreen_mutes_all_apps: bool,
+    #[serde(default)]
+    pub only_mute_playing_apps: bool,
+    #[serde(default)]
+    pub only_mute_while_foreground_playing: bool,
+    #[serde(default)]
+    pub duck_during_voice_chat: bool,
+    #[serde(default)]
+    pub audible_output_devices: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_app_categories")]
+    pub app_categories: HashMap<ProgramPath, AppCategory>,
+    #[serde(default)]
+    pub category_policies: CategoryPolicies,
+    #[serde(default, deserialize_with = "deserialize_conflicts")]
+    pub conflicts: HashMap<ProgramPath, Vec<Rival>>,
+    #[serde(default, deserialize_with = "deserialize_process_rules")]
+    pub process_rules: HashMap<ProgramPath, Vec<ProgramPath>>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AppCategory {
+    Media,
+    Game,
+    Voice,
+    Browser,
+    Other,
+}
+
+impl AppCategory {
+    pub const ALL: [AppCategory; 5] = [
+        AppCategory::Media,
+        AppCategory::Game,
+        AppCategory::Voice,
+        AppCategory::Browser,
+        AppCategory::Other,
+    ];
+
+    /// Guesses the category of a newly managed app from its path.
+    pub fn guess_from_path(program_path: &ProgramPath) -> Self {
+        const KNOWN_APPS: &[(&str, AppCategory)] = &[
+            ("\\spotify.exe", AppCategory::Media),
+            ("\\vlc.exe", AppCategory::Media),
+            ("\\foobar2000.exe", AppCategory::Media),
+            ("\\discord.exe", AppCategory::Voice),
+            ("\\mumble.exe", AppCategory::Voice),
+            ("\\teams.exe", AppCategory::Voice),
+            ("\\ms-teams.exe", AppCategory::Voice),
+            ("\\chrome.exe", AppCategory::Browser),
+            ("\\firefox.exe", AppCategory::Browser),
+            ("\\msedge.exe", AppCategory::Browser),
+            ("\\steamapps\\common\\", AppCategory::Game),
+        ];
+
+        let program_path = program_path.to_lowercase();
+
+        KNOWN_APPS
+            .iter()
+            .find(|(pattern, _)| program_path.contains(pattern))
+            .map(|(_, category)| *category)
+            .unwrap_or(AppCategory::Other)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        AppCategory::ALL
+            .into_iter()
+            .find(|category| category.as_name() == name)
+    }
+
+    /// The name used in the config file.
+    pub fn as_name(&self) -> &'static str {
+        match self {
+            AppCategory::Media => "media",
+            AppCategory::Game => "game",
+            AppCategory::Voice => "voice",
+            AppCategory::Browser => "browser",
+            AppCategory::Other => "other",
+        }
+    }
+}
+
+impl Display for AppCategory {
+    /// The capitalized config name.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (first, rest) = self.as_name().split_at(1);
+        write!(f, "{}{}", first.to_uppercase(), rest)
+    }
+}
+
+/// An app or category whose presence in the foreground causes a managed app to be silenced.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Rival {
+    Category(AppCategory),
+    App(ProgramPath),
+}
+
+impl Rival {
+    fn from_name(name: String) -> Self {
+        match AppCategory::from_name(&name) {
+            Some(category) => Rival::Category(category),
+            None => Rival::App(UniCase::new(SharedStr::from(name))),
+        }
+    }
+
+    fn as_name(&self) -> &str {
+        match self {
+            Rival::Category(category) => category.as_name(),
+            Rival::App(program_path) => program_path.as_str(),
+        }
+    }
+}
+
+/// What happens to a managed app of a category while it is in the background.
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CategoryPolicy {
+    Mute,
+    Duck,
+    Reroute,
+    Never,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+#[serde(default)]
+pub struct CategoryPolicies {
+    pub media: CategoryPolicy,
+    pub game: CategoryPolicy,
+    pub voice: CategoryPolicy,
+    pub browser: CategoryPolicy,
+    pub other: CategoryPolicy,
+}
+
+impl CategoryPolicies {
+    pub fn get(&self, category: AppCategory) -> CategoryPolicy {
+        match category {
+            AppCategory::Media => self.media,
+            AppCategory::Game => self.game,
+            AppCategory::Voice => self.voice,
+            AppCategory::Browser => self.browser,
+            AppCategory::Other => self.other,
+        }
+    }
+}
+
+impl Default for CategoryPolicies {
+    fn default() -> Self {
+        CategoryPolicies {
+            media: CategoryPolicy::Mute,
+            game: CategoryPolicy::Mute,
+            voice: CategoryPolicy::Mute,
+            browser: CategoryPolicy::Mute,
+            other: CategoryPolicy::Mute,
+        }
+    }
 }
 
 impl AnnieConfig {
@@ -22,12 +206,84 @@ impl AnnieConfig {
             enabled: true,
             managed_apps: Default::default(),
             max_recent_apps: 10,
+            audible_recent_apps: default_audible_recent_apps(),
+            duck_volume: default_duck_volume(),
+            reroute_output_device: Default::default(),
+            reroute_apps: Default::default(),
+            keep_visible_apps_audible: false,
+            keep_topmost_apps_audible: false,
+            keep_current_desktop_apps_audible: false,
+            minimize_only_apps: Default::default(),
+            mute_capture_apps: Default::default(),
+            mute_when_idle_secs: 0,
+            mute_when_locked: false,
+            mute_all_apps_when_away: false,
+            fullscreen_mutes_all_apps: false,
+            only_mute_playing_apps: false,
+            only_mute_while_foreground_playing: false,
+            duck_during_voice_chat: false,
+            audible_output_devices: Default::default(),
+            app_categories: Default::default(),
+            category_policies: Default::default(),
+            conflicts: Default::default(),
+            process_rules: Default::default(),
         }
     }
 
+    pub fn get_app_category(&self, program_path: &ProgramPath) -> AppCategory {
+        self.app_categories
+            .get(program_path)
+            .copied()
+            .unwrap_or(AppCategory::Other)
+    }
+
+    /// The policy of an app's category, unless the app is rerouted on its own.
+    pub fn get_app_policy(&self, program_path: &ProgramPath) -> CategoryPolicy {
+        if self.reroute_apps.contains(program_path) {
+            return CategoryPolicy::Reroute;
+        }
+
+        self.category_policies
+            .get(self.get_app_category(program_path))
+    }
+
+    /// Whether every app is left audible while playing through `device_name`, because it
+    /// contains one of `audible_output_devices` (case-insensitive).
+    pub fn is_audible_output_device(&self, device_name: &str) -> bool {
+        let device_name = device_name.to_lowercase();
+
+        self.audible_output_devices
+            .iter()
+            .any(|pattern| device_name.contains(&pattern.to_lowercase()))
+    }
+
+    /// Whether an app with conflict rules is in conflict with `foreground_path`.
+    /// Apps without conflict rules are in conflict with every app.
+    pub fn is_in_conflict(
+        &self,
+        program_path: &ProgramPath,
+        foreground_path: &ProgramPath,
+    ) -> bool {
+        let Some(rivals) = self.conflicts.get(program_path) else { return true };
+        let foreground_category = self.get_app_category(foreground_path);
+
+        rivals.iter().any(|rival| match rival {
+            Rival::Category(category) => *category == foreground_category,
+            Rival::App(rival_path) => rival_path == foreground_path,
+        })
+    }
+
     pub fn load_from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
         let payload = fs::read_to_string(path)?;
-        let config = toml::from_str(&payload)?;
+        let config: AnnieConfig = toml::from_str(&payload)?;
+
+        if !(0.0..=1.0).contains(&config.duck_volume) {
+            anyhow::bail!(
+                "duck_volume must be between 0.0 and 1.0, but is {}",
+                config.duck_volume
+            );
+        }
+
         Ok(config)
     }
 
@@ -38,15 +294,65 @@ impl AnnieConfig {
     }
 }
 
-fn deserialize_managed_apps<'a, D: Deserializer<'a>>(
+fn default_audible_recent_apps() -> usize {
+    1
+}
+
+fn default_duck_volume() -> f64 {
+    0.2
+}
+
+fn deserialize_program_paths<'a, D: Deserializer<'a>>(
     d: D,
 ) -> Result<HashSet<ProgramPath>, D::Error> {
-    let managed_apps: Vec<String> = Deserialize::deserialize(d)?;
-    let managed_apps: HashSet<ProgramPath> = managed_apps
+    let program_paths: Vec<String> = Deserialize::deserialize(d)?;
+    let program_paths: HashSet<ProgramPath> = program_paths
         .into_iter()
         .map(|app_name| UniCase::new(SharedStr::from(app_name)))
         .collect();
-    Ok(managed_apps)
+    Ok(program_paths)
+}
+
+fn deserialize_app_categories<'a, D: Deserializer<'a>>(
+    d: D,
+) -> Result<HashMap<ProgramPath, AppCategory>, D::Error> {
+    let app_categories: HashMap<String, AppCategory> = Deserialize::deserialize(d)?;
+    let app_categories: HashMap<ProgramPath, AppCategory> = app_categories
+        .into_iter()
+        .map(|(app_name, category)| (UniCase::new(SharedStr::from(app_name)), category))
+        .collect();
+    Ok(app_categories)
+}
+
+fn deserialize_conflicts<'a, D: Deserializer<'a>>(
+    d: D,
+) -> Result<HashMap<ProgramPath, Vec<Rival>>, D::Error> {
+    let conflicts: HashMap<String, Vec<String>> = Deserialize::deserialize(d)?;
+    let conflicts: HashMap<ProgramPath, Vec<Rival>> = conflicts
+        .into_iter()
+        .map(|(app_name, rivals)| {
+            let rivals = rivals.into_iter().map(Rival::from_name).collect_vec();
+            (UniCase::new(SharedStr::from(app_name)), rivals)
+        })
+        .collect();
+    Ok(conflicts)
+}
+
+fn deserialize_process_rules<'a, D: Deserializer<'a>>(
+    d: D,
+) -> Result<HashMap<ProgramPath, Vec<ProgramPath>>, D::Error> {
+    let process_rules: HashMap<String, Vec<String>> = Deserialize::deserialize(d)?;
+    let process_rules: HashMap<ProgramPath, Vec<ProgramPath>> = process_rules
+        .into_iter()
+        .map(|(trigger_name, app_names)| {
+            let program_paths = app_names
+                .into_iter()
+                .map(|app_name| UniCase::new(SharedStr::from(app_name)))
+                .collect_vec();
+            (UniCase::new(SharedStr::from(trigger_name)), program_paths)
+        })
+        .collect();
+    Ok(process_rules)
 }
 
 fn serialize_toml_config(config: &AnnieConfig) -> anyhow::Result<String> {
@@ -71,6 +377,20 @@ fn serialize_toml_config(config: &AnnieConfig) -> anyhow::Result<String> {
         Ok(())
     }
 
+    fn write_table_entries<K: Serialize, V: Serialize>(
+        writer: &mut String,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> anyhow::Result<()> {
+        for (key, value) in entries.into_iter() {
+            Serialize::serialize(&key, ValueSerializer::new(writer))?;
+            write!(writer, " = ")?;
+            Serialize::serialize(&value, ValueSerializer::new(writer))?;
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
     let managed_apps = config
         .managed_apps
         .iter()
@@ -87,12 +407,309 @@ fn serialize_toml_config(config: &AnnieConfig) -> anyhow::Result<String> {
     let mut max_recent_apps_seri = String::new();
     write_field(&mut max_recent_apps_seri, &config.max_recent_apps)?;
 
+    let mut audible_recent_apps_seri = String::new();
+    write_field(&mut audible_recent_apps_seri, &config.audible_recent_apps)?;
+
+    let mut duck_volume_seri = String::new();
+    write_field(&mut duck_volume_seri, &config.duck_volume)?;
+
+    let mut reroute_output_device_seri = String::new();
+    write_field(
+        &mut reroute_output_device_seri,
+        &config.reroute_output_device,
+    )?;
+
+    let reroute_apps = config
+        .reroute_apps
+        .iter()
+        .sorted()
+        .map(|program_path| program_path.as_str())
+        .collect_vec();
+
+    let mut reroute_apps_seri = String::new();
+    write_array_field(&mut reroute_apps_seri, &reroute_apps)?;
+
+    let mut keep_visible_apps_audible_seri = String::new();
+    write_field(
+        &mut keep_visible_apps_audible_seri,
+        &config.keep_visible_apps_audible,
+    )?;
+
+    let mut keep_topmost_apps_audible_seri = String::new();
+    write_field(
+        &mut keep_topmost_apps_audible_seri,
+        &config.keep_topmost_apps_audible,
+    )?;
+
+    let mut keep_current_desktop_apps_audible_seri = String::new();
+    write_field(
+        &mut keep_current_desktop_apps_audible_seri,
+        &config.keep_current_desktop_apps_audible,
+    )?;
+
+    let minimize_only_apps = config
+        .minimize_only_apps
+        .iter()
+        .sorted()
+        .map(|program_path| program_path.as_str())
+        .collect_vec();
+
+    let mut minimize_only_apps_seri = String::new();
+    write_array_field(&mut minimize_only_apps_seri, &minimize_only_apps)?;
+
+    let mute_capture_apps = config
+        .mute_capture_apps
+        .iter()
+        .sorted()
+        .map(|program_path| program_path.as_str())
+        .collect_vec();
+
+    let mut mute_capture_apps_seri = String::new();
+    write_array_field(&mut mute_capture_apps_seri, &mute_capture_apps)?;
+
+    let mut mute_when_idle_secs_seri = String::new();
+    write_field(&mut mute_when_idle_secs_seri, &config.mute_when_idle_secs)?;
+
+    let mut mute_when_locked_seri = String::new();
+    write_field(&mut mute_when_locked_seri, &config.mute_when_locked)?;
+
+    let mut mute_all_apps_when_away_seri = String::new();
+    write_field(
+        &mut mute_all_apps_when_away_seri,
+        &config.mute_all_apps_when_away,
+    )?;
+
+    let mut fullscreen_mutes_all_apps_seri = String::new();
+    write_field(
+        &mut fullscreen_mutes_all_apps_seri,
+        &config.fullscreen_mutes_all_apps,
+    )?;
+
+    let mut only_mute_playing_apps_seri = String::new();
+    write_field(
+        &mut only_mute_playing_apps_seri,
+        &config.only_mute_playing_apps,
+    )?;
+
+    let mut only_mute_while_foreground_playing_seri = String::new();
+    write_field(
+        &mut only_mute_while_foreground_playing_seri,
+        &config.only_mute_while_foreground_playing,
+    )?;
+
+    let mut duck_during_voice_chat_seri = String::new();
+    write_field(
+        &mut duck_during_voice_chat_seri,
+        &config.duck_during_voice_chat,
+    )?;
+
+    let mut audible_output_devices_seri = String::new();
+    write_array_field(
+        &mut audible_output_devices_seri,
+        &config.audible_output_devices,
+    )?;
+
+    let app_categories = config
+        .app_categories
+        .iter()
+        .sorted_by_key(|(program_path, _)| *program_path)
+        .map(|(program_path, category)| (program_path.as_str(), category));
+
+    let mut app_categories_seri = String::new();
+    write_table_entries(&mut app_categories_seri, app_categories)?;
+
+    let category_policies = AppCategory::ALL
+        .iter()
+        .map(|category| (category, config.category_policies.get(*category)));
+
+    let mut category_policies_seri = String::new();
+    write_table_entries(&mut category_policies_seri, category_policies)?;
+
+    let conflicts = config
+        .conflicts
+        .iter()
+        .sorted_by_key(|(program_path, _)| *program_path)
+        .map(|(program_path, rivals)| {
+            let rivals = rivals.iter().map(|rival| rival.as_name()).collect_vec();
+            (program_path.as_str(), rivals)
+        });
+
+    let mut conflicts_seri = String::new();
+    write_table_entries(&mut conflicts_seri, conflicts)?;
+
+    let process_rules = config
+        .process_rules
+        .iter()
+        .sorted_by_key(|(trigger_path, _)| *trigger_path)
+        .map(|(trigger_path, program_paths)| {
+            let program_paths = program_paths.iter().map(|p| p.as_str()).collect_vec();
+            (trigger_path.as_str(), program_paths)
+        });
+
+    let mut process_rules_seri = String::new();
+    write_table_entries(&mut process_rules_seri, process_rules)?;
+
     let serialized = format!(
         include_str!("../resource/config-template"),
         enabled = enabled_seri,
         managed_apps = managed_apps_seri,
         max_recent_apps = max_recent_apps_seri,
+        audible_recent_apps = audible_recent_apps_seri,
+        duck_volume = duck_volume_seri,
+        reroute_output_device = reroute_output_device_seri,
+        reroute_apps = reroute_apps_seri,
+        keep_visible_apps_audible = keep_visible_apps_audible_seri,
+        keep_topmost_apps_audible = keep_topmost_apps_audible_seri,
+        keep_current_desktop_apps_audible = keep_current_desktop_apps_audible_seri,
+        minimize_only_apps = minimize_only_apps_seri,
+        mute_capture_apps = mute_capture_apps_seri,
+        mute_when_idle_secs = mute_when_idle_secs_seri,
+        mute_when_locked = mute_when_locked_seri,
+        mute_all_apps_when_away = mute_all_apps_when_away_seri,
+        fullscreen_mutes_all_apps = fullscreen_mutes_all_apps_seri,
+        only_mute_playing_apps = only_mute_playing_apps_seri,
+        only_mute_while_foreground_playing = only_mute_while_foreground_playing_seri,
+        duck_during_voice_chat = duck_during_voice_chat_seri,
+        audible_output_devices = audible_output_devices_seri,
+        app_categories = app_categories_seri,
+        category_policies = category_policies_seri,
+        conflicts = conflicts_seri,
+        process_rules = process_rules_seri,
     );
 
     Ok(serialized)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn path(name: &str) -> ProgramPath {
+        UniCase::new(SharedStr::from(name))
+    }
+
+    fn temp_config_path(name: &str) -> std::path::PathBuf {
+        env::temp_dir().join(format!("annie-test-{}-{}.toml", name, process::id()))
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let spotify = path("C:\\Program Files\\Spotify\\Spotify.exe");
+        let discord = path("C:\\Users\\me\\Discord\\Discord.exe");
+        let obs = path("C:\\Program Files\\obs-studio\\bin\\64bit\\obs64.exe");
+
+        let mut config = AnnieConfig::new_empty();
+        config.managed_apps = HashSet::from([spotify.clone(), discord.clone()]);
+        config.duck_volume = 0.35;
+        config.reroute_output_device = "Headphones".into();
+        config.reroute_apps = HashSet::from([spotify.clone()]);
+        config.mute_capture_apps = HashSet::from([discord.clone()]);
+        config.app_categories = HashMap::from([
+            (spotify.clone(), AppCategory::Media),
+            (discord.clone(), AppCategory::Voice),
+        ]);
+        config.category_policies.media = CategoryPolicy::Duck;
+        config.category_policies.voice = CategoryPolicy::Never;
+        config.conflicts = HashMap::from([(
+            spotify.clone(),
+            vec![
+                Rival::Category(AppCategory::Game),
+                Rival::App(discord.clone()),
+            ],
+        )]);
+        config.process_rules = HashMap::from([(obs.clone(), vec![spotify.clone()])]);
+
+        let config_path = temp_config_path("round-trip");
+        config.save_to_file(&config_path).unwrap();
+        let loaded = AnnieConfig::load_from_file(&config_path);
+        fs::remove_file(&config_path).ok();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.managed_apps, config.managed_apps);
+        assert_eq!(loaded.duck_volume, 0.35);
+        assert_eq!(loaded.reroute_output_device, "Headphones");
+        assert_eq!(loaded.reroute_apps, config.reroute_apps);
+        assert_eq!(loaded.mute_capture_apps, config.mute_capture_apps);
+        assert_eq!(loaded.app_categories, config.app_categories);
+        for category in AppCategory::ALL {
+            assert_eq!(
+                loaded.category_policies.get(category),
+                config.category_policies.get(category)
+            );
+        }
+        assert_eq!(loaded.conflicts, config.conflicts);
+        assert_eq!(loaded.process_rules, config.process_rules);
+    }
+
+    #[test]
+    fn load_rejects_duck_volume_out_of_range() {
+        let mut config = AnnieConfig::new_empty();
+        config.duck_volume = 1.5;
+
+        let config_path = temp_config_path("duck-volume");
+        config.save_to_file(&config_path).unwrap();
+        let loaded = AnnieConfig::load_from_file(&config_path);
+        fs::remove_file(&config_path).ok();
+
+        assert!(loaded.is_err());
+    }
+
+    #[test]
+    fn guess_category_from_path() {
+        let guess = |name| AppCategory::guess_from_path(&path(name));
+
+        assert_eq!(
+            guess("C:\\Users\\me\\AppData\\Roaming\\Spotify\\Spotify.exe"),
+            AppCategory::Media
+        );
+        assert_eq!(
+            guess("C:\\Users\\me\\AppData\\Local\\Discord\\app-1.0\\Discord.exe"),
+            AppCategory::Voice
+        );
+        assert_eq!(
+            guess("C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe"),
+            AppCategory::Browser
+        );
+        assert_eq!(
+            guess("D:\\Steam\\steamapps\\common\\Portal 2\\portal2.exe"),
+            AppCategory::Game
+        );
+        assert_eq!(guess("C:\\Windows\\notepad.exe"), AppCategory::Other);
+    }
+
+    #[test]
+    fn category_names_round_trip() {
+        for category in AppCategory::ALL {
+            assert_eq!(AppCategory::from_name(category.as_name()), Some(category));
+        }
+        assert_eq!(AppCategory::Media.to_string(), "Media");
+        assert_eq!(AppCategory::from_name("Media"), None);
+    }
+
+    #[test]
+    fn conflict_rules() {
+        let spotify = path("C:\\Program Files\\Spotify\\Spotify.exe");
+        let game = path("D:\\Steam\\steamapps\\common\\Portal 2\\portal2.exe");
+        let discord = path("C:\\Users\\me\\Discord\\Discord.exe");
+        let notepad = path("C:\\Windows\\notepad.exe");
+
+        let mut config = AnnieConfig::new_empty();
+        config.app_categories = HashMap::from([(game.clone(), AppCategory::Game)]);
+        config.conflicts = HashMap::from([(
+            spotify.clone(),
+            vec![
+                Rival::Category(AppCategory::Game),
+                Rival::App(discord.clone()),
+            ],
+        )]);
+
+        assert!(config.is_in_conflict(&spotify, &game));
+        assert!(config.is_in_conflict(&spotify, &discord));
+        assert!(!config.is_in_conflict(&spotify, &notepad));
+
+        // apps without conflict rules conflict with every app
+        assert!(config.is_in_conflict(&notepad, &spotify));
+    }
+}
@@ -1,32 +1,30 @@
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Write,
     mem,
     path::{Path, PathBuf},
     process::Command,
-    sync::mpsc::{Receiver, Sender},
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
 };
 
 use flexstr::SharedStr;
 use itertools::Itertools;
 use log::{debug, error, info};
+use msgbox::IconType;
 use unicase::UniCase;
-use winapi::{
-    shared::{minwindef::DWORD, windef::HWND},
-    um::{
-        handleapi::{CloseHandle, INVALID_HANDLE_VALUE},
-        processthreadsapi::OpenProcess,
-        tlhelp32::{
-            CreateToolhelp32Snapshot, Process32First, Process32Next, PROCESSENTRY32,
-            TH32CS_SNAPPROCESS,
-        },
-        winbase::QueryFullProcessImageNameW,
-        winnt::PROCESS_QUERY_INFORMATION,
-    },
-};
+use winapi::shared::windef::HWND;
 
 use crate::{
-    config::AnnieConfig,
+    audio_monitor::AudioMonitorHandle,
+    config::{AnnieConfig, AppCategory, CategoryPolicy},
+    device_listener::DeviceListenerHandle,
     error::{AnnieError, AnnieResult},
-    mute_control::MuteProxy,
+    mute_control::{self, MuteProxy},
+    process::{self, ProcessId},
+    process_monitor::ProcessMonitorHandle,
+    session_listener::SessionListenerHandle,
     tray_application::{TrayEvent, TraySender},
     window::Window,
     window_listener::WindowListenerHandle,
@@ -35,14 +33,41 @@ use crate::{
 #[derive(Debug)]
 pub enum CoreMessage {
     NewForegroundWindow(usize), // can't send raw hwnd
+    WindowMinimized(usize),
+    WindowRestored(usize),
+    WindowLayoutChanged,
+    WorkspaceChanged,
+    SessionLocked(bool),
+    SessionIdle(bool),
+    FullscreenChanged(usize, bool),
+    AudioActivityChanged(ProcessId, bool),
+    CaptureActivityChanged(ProcessId, bool),
+    ProcessStarted(ProcessId),
+    OutputDeviceChanged(Option<String>),
+    AudioDevicesChanged,
+    AudioSessionCreated(ProcessId),
+    ProcessExited(ProcessId),
     SetEnabledGlobal(bool),
     SetEnabledApp(ProgramPath, bool),
+    SetAppCategory(ProgramPath, AppCategory),
+    SetPinnedApp(Option<ProgramPath>),
+    SetExemptApp(ProgramPath, Option<Exemption>),
+    ExpireExemptions,
     OpenConfig,
     ReloadConfig,
     ForceUnmuteAll,
+    ShowStatus,
+    ShowAudioSessions,
     ExitApplication,
 }
 
+/// How long a managed app is kept playing in the background.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Exemption {
+    Session,
+    Minutes(u64),
+}
+
 pub type CoreSender = Sender<CoreMessage>;
 pub type ProgramPath = UniCase<SharedStr>;
 
@@ -50,27 +75,99 @@ pub struct AnnieCore {
     config: AnnieConfig,
     config_path: PathBuf,
     foreground_window: Option<Window>,
+    foreground_fullscreen: bool,
+    focus_history: VecDeque<(ProcessId, ProgramPath)>,
+    visible_apps: HashMap<ProcessId, ProgramPath>,
+    minimized_apps: HashSet<ProgramPath>,
+    pinned_app: Option<ProgramPath>,
+    exempt_apps: HashMap<ProgramPath, Option<Instant>>,
+    session_locked: bool,
+    session_idle: bool,
+    muted_unmanaged_apps: HashSet<ProcessId>,
+    ducked_unmanaged_apps: HashSet<ProcessId>,
+    playing_processes: HashSet<ProcessId>,
+    capturing_processes: HashSet<ProcessId>,
+    active_voice_processes: HashSet<ProcessId>,
+    running_processes: HashMap<ProcessId, ProgramPath>,
+    rule_managed_apps: HashSet<ProgramPath>,
+    output_device: Option<String>,
     receiver: Receiver<CoreMessage>,
     tray_sender: TraySender,
-    listener_thread: Option<WindowListenerHandle>,
+    listener_threads: Option<ListenerThreads>,
     mute_proxy_: Option<MuteProxy>,
 }
 
+/// The threads which report window, session, audio and process events to the core.
+pub struct ListenerThreads {
+    window_listener: WindowListenerHandle,
+    session_listener: SessionListenerHandle,
+    audio_monitor: AudioMonitorHandle,
+    process_monitor: ProcessMonitorHandle,
+    device_listener: DeviceListenerHandle,
+}
+
+impl ListenerThreads {
+    pub fn spawn(core_sender: CoreSender) -> Self {
+        ListenerThreads {
+            window_listener: WindowListenerHandle::spawn(core_sender.clone()),
+            session_listener: SessionListenerHandle::spawn(core_sender.clone()),
+            audio_monitor: AudioMonitorHandle::spawn(core_sender.clone()),
+            process_monitor: ProcessMonitorHandle::spawn(core_sender.clone()),
+            device_listener: DeviceListenerHandle::spawn(core_sender),
+        }
+    }
+
+    fn join(self) {
+        self.window_listener
+            .join()
+            .expect("cannot join listener thread");
+        self.session_listener
+            .join()
+            .expect("cannot join session listener thread");
+        self.audio_monitor
+            .join()
+            .expect("cannot join audio monitor thread");
+        self.process_monitor
+            .join()
+            .expect("cannot join process monitor thread");
+        self.device_listener
+            .join()
+            .expect("cannot join device listener thread");
+    }
+}
+
 impl AnnieCore {
     pub fn run_with_config(
         config_path: impl AsRef<Path>,
+        journal_path: impl AsRef<Path>,
         receiver: Receiver<CoreMessage>,
         tray_sender: TraySender,
-        listener_thread: WindowListenerHandle,
+        listener_threads: ListenerThreads,
     ) -> Result<(), AnnieError> {
         let mut core = AnnieCore {
             config: AnnieConfig::new_empty(),
             config_path: config_path.as_ref().into(),
             foreground_window: None,
+            foreground_fullscreen: false,
+            focus_history: VecDeque::new(),
+            visible_apps: HashMap::new(),
+            minimized_apps: HashSet::new(),
+            pinned_app: None,
+            exempt_apps: HashMap::new(),
+            session_locked: false,
+            session_idle: false,
+            muted_unmanaged_apps: HashSet::new(),
+            ducked_unmanaged_apps: HashSet::new(),
+            playing_processes: HashSet::new(),
+            capturing_processes: HashSet::new(),
+            active_voice_processes: HashSet::new(),
+            running_processes: HashMap::new(),
+            rule_managed_apps: HashSet::new(),
+            output_device: None,
             receiver,
             tray_sender,
-            listener_thread: Some(listener_thread),
-            mute_proxy_: Some(MuteProxy::new()),
+            listener_threads: Some(listener_threads),
+            mute_proxy_: Some(MuteProxy::new(journal_path.as_ref().into())),
         };
 
         if !config_path.as_ref().exists() {
@@ -81,39 +178,194 @@ impl AnnieCore {
 
         // process messages (until ExitApplication is encountered)
         loop {
-            let message = core
-                .receiver
-                .recv()
-                .expect("all core senders closed, did a thread crash?");
+            let message = core.receive_message();
 
             if !core.process_message(message)? {
                 break;
             }
         }
 
-        // join listener thread
-        core.listener_thread
+        // join listener threads
+        core.listener_threads
             .take()
-            .expect("listener thread is empty")
-            .join()
-            .expect("cannot join listener thread");
+            .expect("listener threads are missing")
+            .join();
 
-        // join mute proxy
-        core.mute_proxy_
-            .take()
-            .expect("")
-            .join()
-            .expect("cannot join mute proxy");
+        // undo the changes made to apps, then join mute proxy
+        let mute_proxy = core.mute_proxy_.take().expect("mute proxy is missing");
+        mute_proxy.restore_all();
+        mute_proxy.join().expect("cannot join mute proxy");
 
         Ok(())
     }
 
+    /// Receives the next message, or `ExpireExemptions` once the earliest exemption runs out.
+    fn receive_message(&self) -> CoreMessage {
+        let next_expiry = self.exempt_apps.values().flatten().min();
+
+        let Some(next_expiry) = next_expiry else {
+            return self
+                .receiver
+                .recv()
+                .expect("all core senders closed, did a thread crash?");
+        };
+
+        let timeout = next_expiry.saturating_duration_since(Instant::now());
+
+        match self.receiver.recv_timeout(timeout) {
+            Ok(message) => message,
+            Err(RecvTimeoutError::Timeout) => CoreMessage::ExpireExemptions,
+            Err(RecvTimeoutError::Disconnected) => {
+                panic!("all core senders closed, did a thread crash?")
+            }
+        }
+    }
+
     fn mute_proxy(&self) -> &MuteProxy {
         self.mute_proxy_.as_ref().expect("mute proxy is missing")
     }
 
+    fn session_listener(&self) -> &SessionListenerHandle {
+        &self
+            .listener_threads
+            .as_ref()
+            .expect("listener threads are missing")
+            .session_listener
+    }
+
     fn is_managed(&self, program_path: &ProgramPath) -> bool {
         self.config.managed_apps.contains(program_path)
+            || self.rule_managed_apps.contains(program_path)
+    }
+
+    /// Apps that are managed by a process rule, because the rule's trigger process is running.
+    fn get_rule_managed_apps(&self) -> HashSet<ProgramPath> {
+        if self.config.process_rules.is_empty() {
+            return HashSet::new();
+        }
+
+        let running_paths: HashSet<&ProgramPath> = self.running_processes.values().collect();
+
+        self.config
+            .process_rules
+            .iter()
+            .filter(|(trigger_path, _)| running_paths.contains(trigger_path))
+            .flat_map(|(_, program_paths)| program_paths)
+            .cloned()
+            .collect()
+    }
+
+    /// Whether a managed process should currently be unmuted.
+    /// On audible output devices (if configured), everything is audible. While the user is away,
+    /// nothing is audible. While a fullscreen window is focused (if enabled), only the foreground
+    /// app is audible. Otherwise, exempt apps are always audible.
+    /// While an app is pinned, only the pinned app is audible, regardless of focus. Minimize-only
+    /// apps are audible unless all their windows are minimized. Otherwise, the recently focused
+    /// apps are audible, as well as apps which are not in conflict with the foreground app and
+    /// visible, always-on-top or current desktop apps (if enabled). If the foreground app is
+    /// silent, background apps may stay audible as well (if enabled).
+    fn is_audible(&self, process: ProcessId, program_path: &ProgramPath) -> bool {
+        if self.is_on_audible_device() {
+            return true;
+        }
+
+        if self.is_away() {
+            return false;
+        }
+
+        if self.is_fullscreen_focused() {
+            return self.foreground_window.as_ref().map_or(false, |w| {
+                self.is_same_app(process, program_path, w.process, &w.program_path)
+            });
+        }
+
+        if self.exempt_apps.contains_key(program_path) {
+            return true;
+        }
+
+        match &self.pinned_app {
+            Some(pinned_app) => pinned_app == program_path,
+            None if self.config.minimize_only_apps.contains(program_path) => {
+                !self.minimized_apps.contains(program_path)
+            }
+            None => {
+                let is_recent = self
+                    .get_recently_focused_apps()
+                    .iter()
+                    .any(|(other, path)| self.is_same_app(process, program_path, *other, path));
+                let is_visible = self
+                    .visible_apps
+                    .iter()
+                    .any(|(other, path)| self.is_same_app(process, program_path, *other, path));
+
+                is_recent
+                    || !self.is_in_conflict(program_path)
+                    || is_visible
+                    || self.is_foreground_silent()
+            }
+        }
+    }
+
+    /// Whether `process` belongs to the app of `other_process`. Processes without a window, e.g.
+    /// the audio processes of browsers and Electron apps, belong to any app with the same path.
+    fn is_same_app(
+        &self,
+        process: ProcessId,
+        program_path: &ProgramPath,
+        other_process: ProcessId,
+        other_path: &ProgramPath,
+    ) -> bool {
+        process == other_process
+            || (program_path == other_path && !Window::process_has_window(process.pid))
+    }
+
+    fn is_in_conflict(&self, program_path: &ProgramPath) -> bool {
+        match &self.foreground_window {
+            Some(foreground_window) => self
+                .config
+                .is_in_conflict(program_path, &foreground_window.program_path),
+            None => !self.config.conflicts.contains_key(program_path),
+        }
+    }
+
+    /// Managed apps with conflict rules that are currently in conflict with the foreground app.
+    fn get_conflicting_apps(&self) -> HashSet<ProgramPath> {
+        self.config
+            .conflicts
+            .keys()
+            .filter(|program_path| self.is_managed(program_path))
+            .filter(|program_path| self.is_in_conflict(program_path))
+            .cloned()
+            .collect()
+    }
+
+    /// The foreground app (if managed), followed by the most recently focused other managed apps.
+    /// The foreground app always takes one of the `audible_recent_apps` slots, even if it is not
+    /// managed.
+    fn get_recently_focused_apps(&self) -> Vec<(ProcessId, ProgramPath)> {
+        let foreground_process = self.foreground_window.as_ref().map(|w| w.process);
+        let foreground_app = self
+            .foreground_window
+            .as_ref()
+            .filter(|w| self.is_managed(&w.program_path))
+            .map(|w| (w.process, w.program_path.clone()));
+
+        let other_apps = self
+            .focus_history
+            .iter()
+            .filter(|(process, _)| Some(*process) != foreground_process)
+            .take(self.config.audible_recent_apps.max(1) - 1)
+            .cloned();
+
+        foreground_app.into_iter().chain(other_apps).collect_vec()
+    }
+
+    fn push_focus_history(&mut self, process: ProcessId, program_path: &ProgramPath) {
+        self.focus_history.retain(|(_, path)| path != program_path);
+        self.focus_history
+            .push_front((process, program_path.clone()));
+        self.focus_history
+            .truncate(self.config.audible_recent_apps.max(1));
     }
 
     fn process_message(&mut self, message: CoreMessage) -> AnnieResult<bool> {
@@ -123,13 +375,55 @@ impl AnnieCore {
 
         match message {
             CoreMessage::NewForegroundWindow(hwnd) => self.handle_new_window(hwnd),
+            CoreMessage::WindowMinimized(hwnd) => self.handle_window_minimized(hwnd),
+            CoreMessage::WindowRestored(hwnd) => self.handle_window_restored(hwnd),
+            CoreMessage::WindowLayoutChanged | CoreMessage::WorkspaceChanged => {
+                self.handle_layout_change();
+            }
+            CoreMessage::SessionLocked(locked) => {
+                let was_away = self.is_away();
+                self.session_locked = locked;
+                self.update_away(was_away);
+            }
+            CoreMessage::SessionIdle(idle) => {
+                let was_away = self.is_away();
+                self.session_idle = idle;
+                self.update_away(was_away);
+            }
+            CoreMessage::FullscreenChanged(hwnd, fullscreen) => {
+                self.handle_fullscreen_change(hwnd, fullscreen);
+            }
+            CoreMessage::AudioActivityChanged(process, playing) => {
+                self.handle_audio_activity(process, playing);
+                self.update_voice_activity(process);
+            }
+            CoreMessage::CaptureActivityChanged(process, capturing) => {
+                self.handle_capture_activity(process, capturing);
+            }
+            CoreMessage::ProcessStarted(process) => self.handle_process_started(process),
+            CoreMessage::ProcessExited(process) => self.handle_process_exited(process),
+            CoreMessage::OutputDeviceChanged(device_name) => {
+                self.handle_output_device_change(device_name);
+            }
+            CoreMessage::AudioDevicesChanged => self.handle_audio_devices_change(),
+            CoreMessage::AudioSessionCreated(process) => self.handle_audio_session_created(process),
             CoreMessage::SetEnabledGlobal(enabled) => self.set_enabled_global(enabled)?,
             CoreMessage::SetEnabledApp(app_name, enabled) => {
                 self.set_managed_app(app_name, enabled)?;
             }
+            CoreMessage::SetAppCategory(app_name, category) => {
+                self.set_app_category(app_name, category)?;
+            }
+            CoreMessage::SetPinnedApp(app_name) => self.set_pinned_app(app_name),
+            CoreMessage::SetExemptApp(app_name, exemption) => {
+                self.set_exempt_app(app_name, exemption);
+            }
+            CoreMessage::ExpireExemptions => self.expire_exemptions(),
             CoreMessage::OpenConfig => self.show_config()?,
             CoreMessage::ReloadConfig => self.reload_config()?,
             CoreMessage::ForceUnmuteAll => self.force_unmute_all(),
+            CoreMessage::ShowStatus => self.show_status(),
+            CoreMessage::ShowAudioSessions => self.show_audio_sessions(),
             CoreMessage::ExitApplication => self.exit_app(),
         }
 
@@ -142,57 +436,622 @@ impl AnnieCore {
             Err(_) => return,
         };
 
-        let window_old =
-            self.foreground_window
-                .replace(match Window::new_from_hwnd(hwnd as HWND) {
-                    Ok(w) => w,
-                    Err(_) => return,
-                });
-        let (pid_old, program_path_old) = match window_old {
-            Some(Window {
-                hwnd: _,
-                pid,
-                program_path,
-            }) => (Some(pid), Some(program_path)),
+        let (process_old, program_path_old) = match &self.foreground_window {
+            Some(window_old) => (
+                Some(window_old.process),
+                Some(window_old.program_path.clone()),
+            ),
             None => (None, None),
         };
-        let is_managed_old = program_path_old
-            .as_ref()
-            .map(|path| self.is_managed(path))
-            .unwrap_or(false);
+        let audible_apps_old = self.get_recently_focused_apps();
+        let conflicting_apps_old = self.get_conflicting_apps();
+        let visible_apps_old = self.visible_apps.clone();
+        let was_fullscreen_focused = self.is_fullscreen_focused();
+        let was_foreground_silent = self.is_foreground_silent();
 
         let is_managed_new = self.is_managed(&window_new.program_path);
 
-        // mute old window, unmute new window (if managed)
+        if is_managed_new {
+            self.push_focus_history(window_new.process, &window_new.program_path);
+        }
+
+        debug!("New foreground window: {:?}", &window_new);
+        let process_new = window_new.process;
+        let program_path_new = window_new.program_path.clone();
+        self.foreground_window = Some(window_new);
+        self.foreground_fullscreen = Window::is_fullscreen(hwnd as HWND);
+
+        let is_switching =
+            self.config.enabled && self.pinned_app.is_none() && process_old != Some(process_new);
+
+        // focus changes the z-order, which may reveal or cover other windows. this is refreshed
+        // first, so that apps which stay visible are not muted below, and apps handled below
+        // are not updated twice
 
-        if self.config.enabled && pid_old != Some(window_new.pid) {
-            if is_managed_old {
-                if let Some(pid_old) = pid_old {
-                    self.mute_proxy().mute(pid_old);
+        let handled_processes = if is_switching {
+            audible_apps_old
+                .iter()
+                .map(|(process, _)| *process)
+                .collect()
+        } else {
+            HashSet::new()
+        };
+        self.update_visible_apps(visible_apps_old, &handled_processes);
+
+        // mute apps that are no longer audible (unless exempt), unmute new window
+        // (if managed and nothing is pinned)
+
+        if is_switching {
+            for (process, program_path) in &audible_apps_old {
+                if self.is_managed(program_path) && !self.is_audible(*process, program_path) {
+                    self.silence(*process, program_path);
                 }
             }
 
             if is_managed_new {
-                self.mute_proxy().unmute(window_new.pid, true);
+                if self.is_audible(process_new, &program_path_new) {
+                    self.make_audible(process_new, &program_path_new);
+                } else {
+                    self.silence(process_new, &program_path_new);
+                }
+            }
+
+            // background apps kept audible by a silent foreground app may need to be silenced,
+            // or vice versa
+
+            if was_foreground_silent != self.is_foreground_silent() {
+                self.update_mute_status_all();
+            }
+
+            // apps with conflict rules may start or stop being in conflict with the new window
+
+            let conflicting_apps_new = self.get_conflicting_apps();
+            for program_path in conflicting_apps_old.symmetric_difference(&conflicting_apps_new) {
+                if program_path != &program_path_new {
+                    self.update_mute_status_app(program_path);
+                }
             }
         }
 
+        // focus may enter or leave a fullscreen window
+
+        if was_fullscreen_focused != self.is_fullscreen_focused() {
+            self.update_fullscreen_focus();
+        }
+
         // if the program has changed, send as recent program to tray
 
-        if !window_new.program_path.starts_with("C:\\Windows\\")
-            && Some(&window_new.program_path) != program_path_old.as_ref()
+        if !program_path_new.starts_with("C:\\Windows\\")
+            && Some(&program_path_new) != program_path_old.as_ref()
         {
             self.tray_sender
-                .send_event(TrayEvent::AddRecentApp(
-                    window_new.program_path.clone(),
-                    is_managed_new,
-                ))
+                .send_event(TrayEvent::AddRecentApp(program_path_new, is_managed_new))
                 .map_err(|err| error!("Cannot send to tray: {}", err))
                 .ok();
         }
+    }
 
-        debug!("New foreground window: {:?}", &window_new);
-        self.foreground_window = Some(window_new);
+    fn handle_fullscreen_change(&mut self, hwnd: usize, fullscreen: bool) {
+        let was_fullscreen_focused = self.is_fullscreen_focused();
+
+        match &self.foreground_window {
+            Some(window) if window.hwnd as usize == hwnd => self.foreground_fullscreen = fullscreen,
+            _ => return,
+        }
+
+        if was_fullscreen_focused != self.is_fullscreen_focused() {
+            self.update_fullscreen_focus();
+        }
+    }
+
+    fn handle_audio_activity(&mut self, process: ProcessId, playing: bool) {
+        let was_foreground_silent = self.is_foreground_silent();
+
+        if playing {
+            self.playing_processes.insert(process);
+        } else {
+            self.playing_processes.remove(&process);
+        }
+
+        if !self.config.enabled {
+            return;
+        }
+
+        if was_foreground_silent != self.is_foreground_silent() {
+            if playing {
+                info!("Foreground app started playing, silencing background apps");
+            } else {
+                info!("Foreground app stopped playing, restoring background apps");
+            }
+
+            self.update_mute_status_all();
+            return;
+        }
+
+        if !playing || !self.config.only_mute_playing_apps {
+            return;
+        }
+
+        // silencing is skipped while an app is not playing -> catch up now
+        let Some(program_path) = process.get_program_path() else { return };
+        if self.is_managed(&program_path) && !self.is_audible(process, &program_path) {
+            self.silence(process, &program_path);
+        }
+    }
+
+    fn handle_output_device_change(&mut self, device_name: Option<String>) {
+        let was_on_audible_device = self.is_on_audible_device();
+
+        match &device_name {
+            Some(device_name) => info!("Default output device is {}", device_name),
+            None => info!("No default output device"),
+        }
+
+        self.output_device = device_name;
+
+        if was_on_audible_device == self.is_on_audible_device() || !self.config.enabled {
+            return;
+        }
+
+        if self.is_on_audible_device() {
+            info!("Playing through an audible output device, restoring apps");
+        } else {
+            info!("Left audible output device, muting apps");
+        }
+
+        self.update_unmanaged_apps();
+        self.update_mute_status_all();
+    }
+
+    /// Re-applies the mute state of all apps, since their sessions on a new or changed device
+    /// start out unmuted.
+    fn handle_audio_devices_change(&mut self) {
+        if !self.config.enabled {
+            return;
+        }
+
+        info!("Audio devices changed, re-applying mute states");
+
+        for &process in &self.muted_unmanaged_apps {
+            self.mute_proxy().mute(process);
+        }
+
+        for &process in &self.ducked_unmanaged_apps {
+            self.mute_proxy().duck(process, self.config.duck_volume);
+        }
+
+        self.update_mute_status_all();
+    }
+
+    /// Applies the mute state of a process to its new audio session right away. Windows gives a
+    /// new session the mute state of the program's last session, which is undone first if annie
+    /// left that one muted. This also covers the first session of a newly started process.
+    fn handle_audio_session_created(&self, process: ProcessId) {
+        self.mute_proxy().session_created(process);
+
+        if !self.config.enabled {
+            return;
+        }
+
+        if self.muted_unmanaged_apps.contains(&process) {
+            self.mute_proxy().mute(process);
+            return;
+        }
+
+        if self.ducked_unmanaged_apps.contains(&process) {
+            self.mute_proxy().duck(process, self.config.duck_volume);
+            return;
+        }
+
+        let Some(program_path) = process.get_program_path() else { return };
+        if self.is_managed(&program_path) {
+            debug!("New audio session of {} ({})", program_path, process.pid);
+            self.update_mute_status(process, &program_path);
+        }
+    }
+
+    /// Whether the default output device is one on which every app is left audible.
+    fn is_on_audible_device(&self) -> bool {
+        self.output_device
+            .as_ref()
+            .map(|device_name| self.config.is_audible_output_device(device_name))
+            .unwrap_or(false)
+    }
+
+    fn handle_process_started(&mut self, process: ProcessId) {
+        let Some(program_path) = process.get_program_path() else { return };
+        let is_trigger = self.config.process_rules.contains_key(&program_path);
+
+        self.mute_proxy().process_started(process);
+        self.running_processes.insert(process, program_path);
+
+        if is_trigger {
+            self.update_rule_managed_apps();
+        }
+    }
+
+    fn handle_process_exited(&mut self, process: ProcessId) {
+        // drop everything known about the process, so no state lingers after it
+        self.focus_history
+            .retain(|(recent_process, _)| *recent_process != process);
+        self.visible_apps.remove(&process);
+
+        if self.playing_processes.contains(&process) {
+            self.handle_audio_activity(process, false);
+        }
+        if self.capturing_processes.contains(&process) {
+            self.handle_capture_activity(process, false);
+        }
+        self.update_voice_activity(process);
+        self.muted_unmanaged_apps.remove(&process);
+        self.ducked_unmanaged_apps.remove(&process);
+        self.mute_proxy().process_exited(process);
+
+        let Some(program_path) = self.running_processes.remove(&process) else { return };
+
+        if self.config.process_rules.contains_key(&program_path) {
+            self.update_rule_managed_apps();
+        }
+    }
+
+    /// Updates apps that have started or stopped being managed because a trigger process of a
+    /// process rule has started or exited.
+    fn update_rule_managed_apps(&mut self) {
+        let rule_managed_apps_new = self.get_rule_managed_apps();
+        let rule_managed_apps_old =
+            mem::replace(&mut self.rule_managed_apps, rule_managed_apps_new);
+
+        let added = self
+            .rule_managed_apps
+            .difference(&rule_managed_apps_old)
+            .filter(|program_path| !self.config.managed_apps.contains(*program_path))
+            .cloned()
+            .collect_vec();
+        let removed = rule_managed_apps_old
+            .difference(&self.rule_managed_apps)
+            .filter(|program_path| !self.config.managed_apps.contains(*program_path))
+            .cloned()
+            .collect_vec();
+
+        for program_path in &added {
+            info!("{} is managed by a process rule", program_path);
+        }
+        for program_path in &removed {
+            info!("{} is no longer managed by a process rule", program_path);
+        }
+
+        if !self.config.enabled {
+            return;
+        }
+
+        // apps that are no longer managed are unmuted, unless unmanaged apps are muted as well
+        for program_path in &removed {
+            for process in process::get_processes_from_path(program_path) {
+                self.mute_proxy().unmute(process);
+            }
+        }
+
+        self.update_unmanaged_apps();
+
+        for program_path in &added {
+            self.update_mute_status_app(program_path);
+        }
+    }
+
+    fn handle_capture_activity(&mut self, process: ProcessId, capturing: bool) {
+        if capturing {
+            self.capturing_processes.insert(process);
+        } else {
+            self.capturing_processes.remove(&process);
+        }
+
+        self.update_voice_activity(process);
+    }
+
+    /// Refreshes whether a process is a voice app that is recording or playing audio, and ducks
+    /// or restores all other apps once the first voice app starts or the last one stops.
+    fn update_voice_activity(&mut self, process: ProcessId) {
+        let was_voice_active = self.is_voice_active();
+
+        let is_active = self.playing_processes.contains(&process)
+            || self.capturing_processes.contains(&process);
+        let is_voice_app = is_active
+            && process
+                .get_program_path()
+                .map(|program_path| self.is_voice_app(&program_path))
+                .unwrap_or(false);
+
+        if is_voice_app {
+            self.active_voice_processes.insert(process);
+        } else {
+            self.active_voice_processes.remove(&process);
+        }
+
+        if was_voice_active == self.is_voice_active() || !self.config.enabled {
+            return;
+        }
+
+        if self.is_voice_active() {
+            info!("Voice chat started, ducking other apps");
+        } else {
+            info!("Voice chat ended, restoring apps");
+        }
+
+        self.update_unmanaged_apps();
+        self.update_mute_status_all();
+    }
+
+    /// Whether a voice app is in use, so all other apps are ducked (if configured).
+    fn is_voice_active(&self) -> bool {
+        self.config.duck_during_voice_chat && !self.active_voice_processes.is_empty()
+    }
+
+    fn is_voice_app(&self, program_path: &ProgramPath) -> bool {
+        self.config.get_app_category(program_path) == AppCategory::Voice
+    }
+
+    /// Whether background apps are left audible because the foreground app is silent
+    /// (if configured).
+    fn is_foreground_silent(&self) -> bool {
+        self.config.only_mute_while_foreground_playing
+            && self
+                .foreground_window
+                .as_ref()
+                .map(|w| !self.is_app_playing(w.process, &w.program_path))
+                .unwrap_or(true)
+    }
+
+    /// Whether any process of an app plays audio. Browsers and Electron apps play from child
+    /// processes rather than the one owning the window.
+    fn is_app_playing(&self, process: ProcessId, program_path: &ProgramPath) -> bool {
+        self.playing_processes.iter().any(|playing_process| {
+            *playing_process == process
+                || self.running_processes.get(playing_process) == Some(program_path)
+        })
+    }
+
+    fn is_fullscreen_focused(&self) -> bool {
+        self.config.fullscreen_mutes_all_apps && self.foreground_fullscreen
+    }
+
+    fn update_fullscreen_focus(&mut self) {
+        if !self.config.enabled {
+            return;
+        }
+
+        if self.is_fullscreen_focused() {
+            info!("Fullscreen window focused, muting other apps");
+        } else {
+            info!("Fullscreen window unfocused, restoring apps");
+        }
+
+        self.update_unmanaged_apps();
+        self.update_mute_status_all();
+    }
+
+    fn handle_window_minimized(&mut self, hwnd: usize) {
+        if self.is_minimize_only_window(hwnd) {
+            self.update_minimized_apps();
+        }
+
+        self.handle_layout_change();
+    }
+
+    fn handle_window_restored(&mut self, hwnd: usize) {
+        if self.is_minimize_only_window(hwnd) {
+            self.update_minimized_apps();
+        }
+
+        // restored windows are brought to the foreground
+        self.handle_new_window(hwnd);
+    }
+
+    fn is_minimize_only_window(&self, hwnd: usize) -> bool {
+        Window::new_from_hwnd(hwnd as HWND)
+            .map(|w| self.config.minimize_only_apps.contains(&w.program_path))
+            .unwrap_or(false)
+    }
+
+    /// Refreshes which minimize-only apps have all their windows minimized and updates those
+    /// that have changed.
+    fn update_minimized_apps(&mut self) {
+        let mut minimized = HashSet::new();
+        let mut restored = HashSet::new();
+
+        for window in Window::all_windows() {
+            if self
+                .config
+                .minimize_only_apps
+                .contains(&window.program_path)
+            {
+                if window.minimized {
+                    minimized.insert(window.program_path);
+                } else {
+                    restored.insert(window.program_path);
+                }
+            }
+        }
+
+        let minimized_apps: HashSet<ProgramPath> =
+            minimized.difference(&restored).cloned().collect();
+        let changed_apps = minimized_apps
+            .symmetric_difference(&self.minimized_apps)
+            .cloned()
+            .collect_vec();
+
+        self.minimized_apps = minimized_apps;
+
+        if !self.config.enabled {
+            return;
+        }
+
+        for program_path in changed_apps {
+            if self.is_managed(&program_path) {
+                if self.minimized_apps.contains(&program_path) {
+                    info!("All windows of {} are minimized", &program_path);
+                } else {
+                    info!("Windows of {} are restored", &program_path);
+                }
+
+                self.update_mute_status_app(&program_path);
+            }
+        }
+    }
+
+    fn handle_layout_change(&mut self) {
+        let visible_apps_old = self.visible_apps.clone();
+        self.update_visible_apps(visible_apps_old, &HashSet::new());
+    }
+
+    /// Refreshes the set of visible apps and updates managed apps whose visibility has changed.
+    /// Depending on the config, apps count as visible if they have an uncovered window, an
+    /// always-on-top window, or any window on the current virtual desktop. `handled_processes`
+    /// are left to the caller.
+    fn update_visible_apps(
+        &mut self,
+        visible_apps_old: HashMap<ProcessId, ProgramPath>,
+        handled_processes: &HashSet<ProcessId>,
+    ) {
+        let keep_visible = self.config.keep_visible_apps_audible;
+        let keep_topmost = self.config.keep_topmost_apps_audible;
+        let keep_current_desktop = self.config.keep_current_desktop_apps_audible;
+
+        if !keep_visible && !keep_topmost && !keep_current_desktop {
+            self.visible_apps.clear();
+            return;
+        }
+
+        let mut windows = vec![];
+
+        if keep_visible {
+            windows.extend(Window::unoccluded_windows());
+        }
+
+        if keep_topmost || keep_current_desktop {
+            // windows on other virtual desktops are cloaked by the shell
+            windows.extend(Window::all_windows().into_iter().filter(|w| {
+                let is_topmost = w.topmost && !w.minimized && !w.cloaked;
+                (keep_topmost && is_topmost) || (keep_current_desktop && !w.cloaked)
+            }));
+        }
+
+        self.visible_apps = windows
+            .into_iter()
+            .filter(|w| self.is_managed(&w.program_path))
+            .map(|w| (w.process, w.program_path))
+            .collect();
+
+        if !self.config.enabled || self.pinned_app.is_some() {
+            return;
+        }
+
+        let foreground_process = self.foreground_window.as_ref().map(|w| w.process);
+        let appeared = self
+            .visible_apps
+            .iter()
+            .filter(|(process, _)| !visible_apps_old.contains_key(process));
+        let disappeared = visible_apps_old
+            .iter()
+            .filter(|(process, _)| !self.visible_apps.contains_key(process));
+
+        for (process, program_path) in appeared.chain(disappeared) {
+            if Some(*process) != foreground_process && !handled_processes.contains(process) {
+                debug!("Visibility of {} ({}) changed", program_path, process.pid);
+                self.update_mute_status(*process, program_path);
+            }
+        }
+    }
+
+    /// Whether the user is idle or has locked the screen (if configured to mute in this case).
+    fn is_away(&self) -> bool {
+        (self.session_locked && self.config.mute_when_locked)
+            || (self.session_idle && self.config.mute_when_idle_secs > 0)
+    }
+
+    fn update_away(&mut self, was_away: bool) {
+        let is_away = self.is_away();
+
+        if is_away == was_away || !self.config.enabled {
+            return;
+        }
+
+        if is_away {
+            info!("User is away, muting apps");
+        } else {
+            info!("User is back, restoring apps");
+        }
+
+        self.update_unmanaged_apps();
+        self.update_mute_status_all();
+    }
+
+    /// Mutes, ducks or restores apps which are not managed. These are only muted while the user
+    /// is away or a fullscreen window is focused, and only ducked during voice chat, if
+    /// configured.
+    fn update_unmanaged_apps(&mut self) {
+        let mute_unmanaged = self.config.enabled
+            && !self.is_on_audible_device()
+            && ((self.is_away() && self.config.mute_all_apps_when_away)
+                || self.is_fullscreen_focused());
+        let duck_unmanaged = self.config.enabled && self.is_voice_active();
+
+        let spared_process = match self.is_away() {
+            true => None,
+            false => self.foreground_window.as_ref().map(|w| w.process),
+        };
+
+        let unmanaged_windows = if mute_unmanaged || duck_unmanaged {
+            Window::all_windows()
+                .into_iter()
+                .filter(|w| !self.is_managed(&w.program_path))
+                .collect_vec()
+        } else {
+            vec![]
+        };
+
+        let processes_to_mute: HashSet<ProcessId> = if mute_unmanaged {
+            unmanaged_windows
+                .iter()
+                .map(|w| w.process)
+                .filter(|process| Some(*process) != spared_process)
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        // voice chat ducks every other app, including the focused one
+        let processes_to_duck: HashSet<ProcessId> = if duck_unmanaged {
+            unmanaged_windows
+                .iter()
+                .filter(|w| !self.is_voice_app(&w.program_path))
+                .map(|w| w.process)
+                .filter(|process| !processes_to_mute.contains(process))
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        for &process in processes_to_mute.difference(&self.muted_unmanaged_apps) {
+            self.mute_proxy().mute(process);
+        }
+
+        for &process in processes_to_duck.difference(&self.ducked_unmanaged_apps) {
+            self.mute_proxy().duck(process, self.config.duck_volume);
+        }
+
+        let silenced_old = self
+            .muted_unmanaged_apps
+            .union(&self.ducked_unmanaged_apps)
+            .copied()
+            .collect::<HashSet<_>>();
+        for &process in &silenced_old {
+            if !processes_to_mute.contains(&process) && !processes_to_duck.contains(&process) {
+                self.mute_proxy().unmute(process);
+            }
+        }
+
+        self.muted_unmanaged_apps = processes_to_mute;
+        self.ducked_unmanaged_apps = processes_to_duck;
     }
 
     fn set_enabled_global(&mut self, enabled: bool) -> AnnieResult<()> {
@@ -204,9 +1063,12 @@ impl AnnieCore {
         self.save_config()?;
 
         if enabled {
+            self.update_unmanaged_apps();
             self.update_mute_status_all();
         } else {
-            self.force_unmute_all();
+            self.mute_proxy().restore_all();
+            self.muted_unmanaged_apps.clear();
+            self.ducked_unmanaged_apps.clear();
         }
 
         Ok(())
@@ -217,20 +1079,25 @@ impl AnnieCore {
             // update mute status on all processes with this path
             info!("Added {} to managed apps", &program_path);
 
-            let foreground_pid = self.foreground_window.as_ref().map(|w| w.pid);
-
-            for pid in Self::get_pids_from_path(&program_path) {
-                if Some(pid) == foreground_pid {
-                    self.mute_proxy().unmute(pid, false);
-                } else {
-                    self.mute_proxy().mute(pid);
-                }
+            if !self.config.app_categories.contains_key(&program_path) {
+                let category = AppCategory::guess_from_path(&program_path);
+                info!("Assigned category {} to {}", category, &program_path);
+                self.config
+                    .app_categories
+                    .insert(program_path.clone(), category);
+                self.send_app_category_to_tray(&program_path);
             }
+
+            self.update_mute_status_app(&program_path);
         } else if !managed && self.config.managed_apps.remove(&program_path) {
             // unmute every process with this path
             info!("Removed {} from managed apps", &program_path);
-            for pid in Self::get_pids_from_path(&program_path) {
-                self.mute_proxy().unmute(pid, false);
+            if self.is_managed(&program_path) {
+                info!("{} stays managed by a process rule", &program_path);
+            } else {
+                for process in process::get_processes_from_path(&program_path) {
+                    self.mute_proxy().unmute(process);
+                }
             }
         }
 
@@ -239,6 +1106,251 @@ impl AnnieCore {
         Ok(())
     }
 
+    fn set_app_category(
+        &mut self,
+        program_path: ProgramPath,
+        category: AppCategory,
+    ) -> AnnieResult<()> {
+        if self.config.app_categories.get(&program_path) == Some(&category) {
+            return Ok(());
+        }
+
+        info!("Assigned category {} to {}", category, &program_path);
+        self.config
+            .app_categories
+            .insert(program_path.clone(), category);
+        self.save_config()?;
+
+        if self.config.enabled && self.is_managed(&program_path) {
+            self.update_mute_status_app(&program_path);
+        }
+
+        self.send_app_category_to_tray(&program_path);
+
+        Ok(())
+    }
+
+    fn send_app_category_to_tray(&self, program_path: &ProgramPath) {
+        self.tray_sender
+            .send_event(TrayEvent::UpdateAppCategory(
+                program_path.clone(),
+                self.config.get_app_category(program_path),
+            ))
+            .map_err(|err| error!("Cannot send to tray: {}", err))
+            .ok();
+    }
+
+    fn set_pinned_app(&mut self, program_path: Option<ProgramPath>) {
+        if program_path == self.pinned_app {
+            return;
+        }
+
+        match &program_path {
+            Some(program_path) => info!("Pinned audio to {}", program_path),
+            None => info!("Unpinned audio"),
+        }
+
+        self.pinned_app = program_path;
+
+        if self.config.enabled {
+            self.update_mute_status_all();
+        }
+
+        self.tray_sender
+            .send_event(TrayEvent::UpdatePinnedApp(self.pinned_app.clone()))
+            .map_err(|err| error!("Cannot send to tray: {}", err))
+            .ok();
+    }
+
+    fn set_exempt_app(&mut self, program_path: ProgramPath, exemption: Option<Exemption>) {
+        match exemption {
+            Some(Exemption::Session) => {
+                info!("Exempted {} from muting for this session", &program_path);
+                self.exempt_apps.insert(program_path.clone(), None);
+            }
+            Some(Exemption::Minutes(minutes)) => {
+                info!("Exempted {} from muting for {} min", &program_path, minutes);
+                let expiry = Instant::now() + Duration::from_secs(minutes * 60);
+                self.exempt_apps.insert(program_path.clone(), Some(expiry));
+            }
+            None => {
+                if self.exempt_apps.remove(&program_path).is_none() {
+                    return;
+                }
+                info!("Removed muting exemption of {}", &program_path);
+            }
+        }
+
+        if self.config.enabled && self.is_managed(&program_path) {
+            self.update_mute_status_app(&program_path);
+        }
+
+        self.send_exempt_apps_to_tray();
+    }
+
+    fn expire_exemptions(&mut self) {
+        let now = Instant::now();
+        let expired = self
+            .exempt_apps
+            .iter()
+            .filter(|(_, expiry)| matches!(expiry, Some(expiry) if *expiry <= now))
+            .map(|(program_path, _)| program_path.clone())
+            .collect_vec();
+
+        for program_path in expired {
+            info!("Muting exemption of {} expired", &program_path);
+            self.exempt_apps.remove(&program_path);
+
+            if self.config.enabled && self.is_managed(&program_path) {
+                self.update_mute_status_app(&program_path);
+            }
+        }
+
+        self.send_exempt_apps_to_tray();
+    }
+
+    fn send_exempt_apps_to_tray(&self) {
+        self.tray_sender
+            .send_event(TrayEvent::UpdateExemptApps(self.exempt_apps.clone()))
+            .map_err(|err| error!("Cannot send to tray: {}", err))
+            .ok();
+    }
+
+    fn show_status(&self) {
+        let mut body = String::new();
+
+        let enabled = if self.config.enabled { "yes" } else { "no" };
+        writeln!(body, "Muting enabled: {}", enabled).ok();
+
+        match &self.output_device {
+            Some(device_name) if self.is_on_audible_device() => {
+                writeln!(body, "Output device: {} (everything audible)", device_name).ok()
+            }
+            Some(device_name) => writeln!(body, "Output device: {}", device_name).ok(),
+            None => writeln!(body, "Output device: none").ok(),
+        };
+
+        let foreground_app = self.foreground_window.as_ref().map(|w| &w.program_path);
+        match foreground_app {
+            Some(program_path) => writeln!(body, "Foreground app: {}", program_path).ok(),
+            None => writeln!(body, "Foreground app: none").ok(),
+        };
+
+        match &self.pinned_app {
+            Some(program_path) => writeln!(body, "Pinned app: {}", program_path).ok(),
+            None => writeln!(body, "Pinned app: none").ok(),
+        };
+
+        if self.config.duck_during_voice_chat {
+            let voice_active = if self.is_voice_active() { "yes" } else { "no" };
+            writeln!(body, "Voice chat active: {}", voice_active).ok();
+        }
+
+        if self.config.audible_recent_apps > 1 {
+            writeln!(body, "\nRecently focused apps:").ok();
+            for (process, program_path) in self.get_recently_focused_apps() {
+                writeln!(body, "{} ({})", program_path, process.pid).ok();
+            }
+        }
+
+        if !self.config.process_rules.is_empty() {
+            writeln!(body, "\nApps managed by process rules:").ok();
+            if self.rule_managed_apps.is_empty() {
+                writeln!(body, "none").ok();
+            }
+            for program_path in self.rule_managed_apps.iter().sorted() {
+                writeln!(body, "{}", program_path).ok();
+            }
+        }
+
+        writeln!(body, "\nExempt apps:").ok();
+        if self.exempt_apps.is_empty() {
+            writeln!(body, "none").ok();
+        }
+        let now = Instant::now();
+        for (program_path, expiry) in self.exempt_apps.iter().sorted() {
+            match expiry {
+                Some(expiry) => {
+                    let minutes_left = expiry.saturating_duration_since(now).as_secs() / 60 + 1;
+                    writeln!(body, "{} ({} min left)", program_path, minutes_left).ok()
+                }
+                None => writeln!(body, "{} (this session)", program_path).ok(),
+            };
+        }
+
+        writeln!(body, "\nApps playing audio:").ok();
+        if self.playing_processes.is_empty() {
+            writeln!(body, "none").ok();
+        }
+        for &process in self.playing_processes.iter().sorted() {
+            match process.get_program_path() {
+                Some(program_path) => writeln!(body, "{} ({})", program_path, process.pid).ok(),
+                None => writeln!(body, "unknown ({})", process.pid).ok(),
+            };
+        }
+
+        info!("Status:\n{}", &body);
+
+        thread::spawn(move || {
+            msgbox::create("Annie status", &body, IconType::Info)
+                .expect("cannot create message box");
+        });
+    }
+
+    fn show_audio_sessions(&self) {
+        let mut body = String::new();
+
+        // pid 0 is the system sounds session
+        let sessions = mute_control::get_audio_sessions()
+            .into_iter()
+            .filter(|session| session.pid != 0)
+            .sorted_by_key(|session| (session.device.clone(), session.pid))
+            .collect_vec();
+
+        if sessions.is_empty() {
+            writeln!(body, "No audio sessions").ok();
+        }
+
+        for (device, device_sessions) in &sessions.iter().group_by(|session| &session.device) {
+            writeln!(body, "{}:", device).ok();
+
+            for session in device_sessions {
+                match &session.program_path {
+                    Some(program_path) => write!(body, "{}", program_path).ok(),
+                    None => write!(body, "unknown").ok(),
+                };
+                if !session.display_name.is_empty() {
+                    write!(body, " \"{}\"", session.display_name).ok();
+                }
+                write!(
+                    body,
+                    " ({}): volume {:.0}%",
+                    session.pid,
+                    session.volume * 100.0
+                )
+                .ok();
+                if session.muted {
+                    write!(body, ", muted").ok();
+                }
+                if let Some(program_path) = &session.program_path {
+                    if self.is_managed(program_path) {
+                        write!(body, ", managed").ok();
+                    }
+                }
+                writeln!(body).ok();
+            }
+
+            writeln!(body).ok();
+        }
+
+        info!("Audio sessions:\n{}", &body);
+
+        thread::spawn(move || {
+            msgbox::create("Annie audio sessions", &body, IconType::Info)
+                .expect("cannot create message box");
+        });
+    }
+
     fn show_config(&self) -> AnnieResult<()> {
         // explorer returns exit code 1 for some reason
         Command::new("explorer")
@@ -272,12 +1384,34 @@ impl AnnieCore {
             }
         })?;
 
-        self.force_unmute_all();
+        self.mute_proxy().restore_all();
+        self.muted_unmanaged_apps.clear();
+        self.ducked_unmanaged_apps.clear();
+
+        self.rule_managed_apps = self.get_rule_managed_apps();
+
+        self.session_listener()
+            .set_idle_timeout(self.config.mute_when_idle_secs);
+
+        self.update_unmanaged_apps();
+
+        if self.config.enabled
+            && (self.pinned_app.is_some()
+                || self.is_away()
+                || self.is_fullscreen_focused()
+                || self.is_voice_active())
+        {
+            self.update_mute_status_all();
+        }
+
+        self.minimized_apps.clear();
+        self.update_minimized_apps();
 
         self.tray_sender
             .send_event(TrayEvent::UpdateFromConfig {
                 enabled: self.config.enabled,
                 managed_apps: self.config.managed_apps.clone(),
+                app_categories: self.config.app_categories.clone(),
                 max_recent_apps: self.config.max_recent_apps,
             })
             .map_err(|err| error!("Cannot send to tray: {}", err))
@@ -288,73 +1422,97 @@ impl AnnieCore {
         Ok(())
     }
 
+    /// Unmutes every windowed app, including apps the user muted by hand.
     fn force_unmute_all(&self) {
         let all_windows = Window::all_windows();
-        let mut pids = all_windows.into_iter().map(|w| w.pid).collect_vec();
-        pids.sort_unstable();
-        pids.dedup();
+        let mut processes = all_windows.into_iter().map(|w| w.process).collect_vec();
+        processes.sort_unstable();
+        processes.dedup();
 
-        for pid in pids {
-            self.mute_proxy().unmute(pid, false)
+        for process in processes {
+            self.mute_proxy().force_unmute(process)
         }
     }
 
     fn exit_app(&self) {}
 
-    fn update_mute_status_all(&self) {
-        let all_windows = Window::all_windows();
-        let foreground_pid = self.foreground_window.as_ref().map(|win| win.pid);
-        let pids = all_windows.into_iter().map(|w| w.pid);
+    /// Mutes, ducks or reroutes a managed process that is not audible, depending on its category
+    /// policy. Its recording is muted as well, if configured.
+    fn silence(&self, process: ProcessId, program_path: &ProgramPath) {
+        self.silence_playback(process, program_path);
 
-        for pid in pids {
-            if Some(pid) == foreground_pid {
-                self.mute_proxy().unmute(pid, false);
-            } else {
-                self.mute_proxy().mute(pid);
-            }
+        // after silencing playback, since ducking restores the recording
+        if self.config.mute_capture_apps.contains(program_path) {
+            self.mute_proxy().mute_capture(process);
         }
     }
 
-    fn get_pids_from_path(program_path: &ProgramPath) -> Vec<DWORD> {
-        unsafe {
-            let mut pids = vec![];
+    fn silence_playback(&self, process: ProcessId, program_path: &ProgramPath) {
+        let policy = self.config.get_app_policy(program_path);
 
-            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
-            if snapshot == INVALID_HANDLE_VALUE {
-                error!("Failed to retrieve process snapshot");
-                return pids;
-            }
+        if policy != CategoryPolicy::Never
+            && self.config.only_mute_playing_apps
+            && !self.playing_processes.contains(&process)
+        {
+            debug!(
+                "Not silencing {} ({}), it is not playing",
+                program_path, process.pid
+            );
+            return;
+        }
 
-            let mut process_entry: PROCESSENTRY32 = mem::zeroed();
-            process_entry.dwSize = mem::size_of::<PROCESSENTRY32>() as _;
-            let mut hresult = Process32First(snapshot, &mut process_entry);
+        match policy {
+            CategoryPolicy::Mute => self.mute_proxy().mute(process),
+            CategoryPolicy::Duck => self.mute_proxy().duck(process, self.config.duck_volume),
+            CategoryPolicy::Reroute => self
+                .mute_proxy()
+                .reroute(process, &self.config.reroute_output_device),
+            CategoryPolicy::Never => self.make_audible(process, program_path),
+        }
+    }
 
-            let target_path = program_path.as_str().encode_utf16().collect_vec();
-            let mut process_path_buf = [0u16; 1024];
+    /// Unmutes a managed process, or ducks it while a voice app is in use.
+    fn make_audible(&self, process: ProcessId, program_path: &ProgramPath) {
+        if self.is_voice_active() && !self.is_voice_app(program_path) {
+            self.mute_proxy().duck(process, self.config.duck_volume);
+        } else {
+            self.mute_proxy().unmute(process);
+        }
+    }
 
-            while hresult > 0 {
-                let handle = OpenProcess(PROCESS_QUERY_INFORMATION, 0, process_entry.th32ProcessID);
-                let mut buf_size: DWORD = process_path_buf.len() as _;
-                let ok = QueryFullProcessImageNameW(
-                    handle,
-                    0,
-                    process_path_buf.as_mut_ptr(),
-                    &mut buf_size,
-                );
-                if ok > 0 && target_path == process_path_buf[..(buf_size as _)] {
-                    pids.push(process_entry.th32ProcessID);
-                }
-                CloseHandle(handle);
-                hresult = Process32Next(snapshot, &mut process_entry);
-            }
+    fn update_mute_status(&self, process: ProcessId, program_path: &ProgramPath) {
+        if self.is_audible(process, program_path) {
+            self.make_audible(process, program_path);
+        } else {
+            self.silence(process, program_path);
+        }
+    }
 
-            pids
+    fn update_mute_status_app(&self, program_path: &ProgramPath) {
+        for process in process::get_processes_from_path(program_path) {
+            self.update_mute_status(process, program_path);
+        }
+    }
+
+    fn update_mute_status_all(&self) {
+        let all_windows = Window::all_windows();
+        let windows = all_windows
+            .into_iter()
+            .filter(|w| self.is_managed(&w.program_path))
+            .unique_by(|w| w.process);
+
+        for window in windows {
+            self.update_mute_status(window.process, &window.program_path);
         }
     }
 }
 
 impl Drop for AnnieCore {
     fn drop(&mut self) {
-        self.force_unmute_all();
+        // the mute proxy is already gone after a regular exit
+        if let Some(mute_proxy) = self.mute_proxy_.take() {
+            mute_proxy.restore_all();
+            mute_proxy.join().ok();
+        }
     }
 }
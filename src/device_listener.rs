@@ -0,0 +1,170 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use log::info;
+use once_cell::sync::Lazy;
+use winapi::shared::minwindef::{BOOL, DWORD, FALSE};
+
+use crate::{
+    core::{CoreMessage, CoreSender},
+    mute_control,
+    process::ProcessId,
+};
+
+extern "C" {
+    fn RegisterDeviceChangeCallback(callback: extern "C" fn()) -> BOOL;
+    fn UnregisterDeviceChangeCallback();
+    fn RegisterSessionCreatedCallback(callback: extern "C" fn(DWORD)) -> BOOL;
+    fn UnregisterSessionCreatedCallback();
+}
+
+// a device change fires several notifications, and apps need a moment to move their streams to
+// the new device -> report once after they settle
+const DEVICE_SETTLE_DELAY: Duration = Duration::from_millis(1000);
+
+enum Notification {
+    DeviceChanged,
+    SessionCreated(ProcessId),
+}
+
+// the notification callbacks don't accept custom data -> communicate via a static
+static NOTIFICATION_SENDER: Lazy<Mutex<Option<Sender<Notification>>>> =
+    Lazy::new(|| Mutex::new(None));
+
+fn send_notification(notification: Notification) {
+    let Some(lock) = NOTIFICATION_SENDER.lock().ok() else { return };
+    if let Some(notification_sender) = &*lock {
+        notification_sender.send(notification).ok();
+    }
+}
+
+extern "C" fn device_change_callback() {
+    send_notification(Notification::DeviceChanged);
+}
+
+extern "C" fn session_created_callback(pid: DWORD) {
+    // look up the process right away, while it is still running
+    if let Some(process) = ProcessId::from_pid(pid) {
+        send_notification(Notification::SessionCreated(process));
+    }
+}
+
+unsafe fn register_session_notifications() {
+    if RegisterSessionCreatedCallback(session_created_callback) == FALSE {
+        panic!("could not register for audio session notifications");
+    }
+}
+
+/// Reports the default output device to the core if it is not `output_device_id` anymore.
+fn update_default_device(core_sender: &CoreSender, output_device_id: &mut Option<String>) {
+    let output_device = mute_control::get_default_output_device();
+    let output_device_id_new = output_device.as_ref().map(|device| device.id.clone());
+
+    if output_device_id_new == *output_device_id {
+        return;
+    }
+
+    *output_device_id = output_device_id_new;
+    let device_name = output_device.map(|device| device.name);
+    core_sender
+        .send(CoreMessage::OutputDeviceChanged(device_name))
+        .ok();
+}
+
+fn device_listener_loop(core_sender: CoreSender, receiver: Receiver<Notification>) {
+    info!("Device listener start");
+
+    unsafe {
+        if RegisterDeviceChangeCallback(device_change_callback) == FALSE {
+            panic!("could not register for audio device notifications");
+        }
+
+        register_session_notifications();
+    }
+
+    let mut device_change_pending = false;
+    let mut output_device_id = None;
+    update_default_device(&core_sender, &mut output_device_id);
+
+    loop {
+        let notification = if device_change_pending {
+            receiver.recv_timeout(DEVICE_SETTLE_DELAY)
+        } else {
+            receiver.recv().map_err(RecvTimeoutError::from)
+        };
+
+        let message = match notification {
+            Ok(Notification::SessionCreated(process)) => CoreMessage::AudioSessionCreated(process),
+            Ok(Notification::DeviceChanged) => {
+                device_change_pending = true;
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                device_change_pending = false;
+
+                // sessions are watched per device -> cover new devices as well
+                unsafe {
+                    UnregisterSessionCreatedCallback();
+                    register_session_notifications();
+                }
+
+                update_default_device(&core_sender, &mut output_device_id);
+                CoreMessage::AudioDevicesChanged
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        if core_sender.send(message).is_err() {
+            break;
+        }
+    }
+
+    unsafe {
+        UnregisterSessionCreatedCallback();
+        UnregisterDeviceChangeCallback();
+    }
+
+    info!("Device listener exit");
+}
+
+/// Reports to the core whenever output devices are plugged in, removed or the default output
+/// device changes, and whenever a process opens a new audio session.
+pub struct DeviceListenerHandle {
+    listener_thread: JoinHandle<()>,
+}
+
+impl DeviceListenerHandle {
+    pub fn spawn(core_sender: CoreSender) -> Self {
+        let (notification_sender, notification_receiver) = mpsc::channel();
+
+        let old_sender = NOTIFICATION_SENDER
+            .lock()
+            .expect("cannot lock notification sender mutex")
+            .replace(notification_sender);
+
+        assert!(old_sender.is_none(), "dangling notification sender");
+
+        DeviceListenerHandle {
+            listener_thread: thread::spawn(move || {
+                device_listener_loop(core_sender, notification_receiver)
+            }),
+        }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        // dropping the sender ends the listener loop
+        NOTIFICATION_SENDER
+            .lock()
+            .expect("cannot lock notification sender mutex")
+            .take()
+            .expect("notification sender is empty during device listener exit");
+
+        self.listener_thread.join()
+    }
+}
@@ -1,9 +1,15 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod audio_monitor;
 mod config;
 mod core;
+mod device_listener;
 mod error;
 mod mute_control;
+mod mute_journal;
+mod process;
+mod process_monitor;
+mod session_listener;
 mod tray_application;
 mod window;
 mod window_listener;
@@ -21,7 +27,7 @@ use msgbox::IconType;
 use simplelog::{ColorChoice, Config, TermLogger, TerminalMode, WriteLogger};
 use single_instance::SingleInstance;
 
-use crate::core::AnnieCore;
+use crate::core::{AnnieCore, ListenerThreads};
 
 fn main() {
     let Some(_instance_lock) = get_instance_lock() else { return };
@@ -30,6 +36,7 @@ fn main() {
 
     let data_dir = get_or_create_data_dir();
     let config_path = data_dir.join("annie.toml");
+    let journal_path = data_dir.join("annie_journal.toml");
 
     setup_logger(&data_dir);
 
@@ -37,9 +44,16 @@ fn main() {
 
     let (core_sender, core_receiver) = mpsc::channel();
     let (_tray_thread, tray_sender) = tray_application::create_tray_thread(core_sender.clone());
-    let listener_thread = window_listener::WindowListenerHandle::spawn(core_sender);
-
-    AnnieCore::run_with_config(config_path, core_receiver, tray_sender, listener_thread).unwrap();
+    let listener_threads = ListenerThreads::spawn(core_sender);
+
+    AnnieCore::run_with_config(
+        config_path,
+        journal_path,
+        core_receiver,
+        tray_sender,
+        listener_threads,
+    )
+    .unwrap();
 
     info!("Annie exit"); // TODO: This is not reached - why?
 }
@@ -4,48 +4,154 @@
 #include <audioclient.h>
 #include <mmdeviceapi.h>
 #include <audiopolicy.h>
+#include <endpointvolume.h>
+#include <inspectable.h>
+#include <roapi.h>
+#include <winstring.h>
+#include <initguid.h>
+#include <functiondiscoverykeys_devpkey.h>
+#include <strsafe.h>
 
 #include "mute_control.hpp"
 
+// CoUninitialize must only balance a CoInitializeEx that succeeded, e.g. not one that failed with
+// RPC_E_CHANGED_MODE because the thread already uses another concurrency model
+#define CO_INITIALIZE() HRESULT hrCoInit = CoInitializeEx(NULL, 0)
+#define CO_UNINITIALIZE() if (SUCCEEDED(hrCoInit)) { CoUninitialize(); }
+
 #define ASSERT_HR(hr) \
     if (FAILED(hr)) { \
-        CoUninitialize(); \
+        CO_UNINITIALIZE(); \
         return FALSE; \
     }
 
-BOOL SetProcessMute(DWORD dwPID, BOOL bMute)
+// Undocumented interface behind the per-app output device setting of the Windows volume mixer
+struct IAudioPolicyConfigFactory : public IInspectable
+{
+    // Unused methods, only declared to get the vtable layout right
+    virtual HRESULT STDMETHODCALLTYPE __add_CtxVolumeChange() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __remove_CtxVolumeChanged() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __add_RingerVibrateStateChanged() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __remove_RingerVibrateStateChange() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __SetVolumeGroupGainForId() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __GetVolumeGroupGainForId() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __GetActiveVolumeGroupForEndpointId() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __GetVolumeGroupsForEndpoint() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __GetCurrentVolumeContext() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __SetVolumeGroupMuteForId() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __GetVolumeGroupMuteForId() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __SetRingerVibrateState() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __GetRingerVibrateState() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __SetPreferredChatApplication() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __ResetPreferredChatApplication() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __GetPreferredChatApplication() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __GetCurrentChatApplications() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __add_ChatContextChanged() = 0;
+    virtual HRESULT STDMETHODCALLTYPE __remove_ChatContextChanged() = 0;
+
+    virtual HRESULT STDMETHODCALLTYPE SetPersistedDefaultAudioEndpoint(UINT processId, EDataFlow flow, ERole role, HSTRING deviceId) = 0;
+    virtual HRESULT STDMETHODCALLTYPE GetPersistedDefaultAudioEndpoint(UINT processId, EDataFlow flow, ERole role, HSTRING* pDeviceId) = 0;
+    virtual HRESULT STDMETHODCALLTYPE ClearAllPersistedApplicationDefaultEndpoints() = 0;
+};
+
+// The interface ID changed with Windows 10 21H2
+static const IID IID_IAudioPolicyConfigFactory = { 0xab3d4648, 0xe242, 0x459f, { 0xb0, 0x2f, 0x54, 0x1c, 0x70, 0x30, 0x63, 0x24 } };
+static const IID IID_IAudioPolicyConfigFactoryLegacy = { 0x2a59116d, 0x6c4f, 0x45e0, { 0xa7, 0x4f, 0x70, 0x7e, 0x3f, 0xef, 0x92, 0x58 } };
+
+#define AUDIO_POLICY_CONFIG_CLASS L"Windows.Media.Internal.AudioPolicyConfig"
+#define MMDEVAPI_PREFIX L"\\\\?\\SWD#MMDEVAPI#"
+#define MMDEVAPI_RENDER_SUFFIX L"#{e6327cad-dcec-4949-ae8a-991e976a79d2}"
+
+// Gets the ID and friendly name (e.g. "Speakers (Realtek Audio)") of an endpoint
+static HRESULT GetDeviceInfo(IMMDevice* pDevice, WCHAR* pszId, UINT cchId, WCHAR* pszName, UINT cchName)
 {
-    CoInitializeEx(NULL, 0);
     HRESULT hr = S_OK;
 
-    // Get the audio endpoint for the process
+    LPWSTR pszDeviceId = NULL;
+    hr = pDevice->GetId(&pszDeviceId);
+    if (FAILED(hr)) {
+        return hr;
+    }
+    lstrcpynW(pszId, pszDeviceId, cchId);
+    CoTaskMemFree(pszDeviceId);
+
+    IPropertyStore* pProperties = NULL;
+    hr = pDevice->OpenPropertyStore(STGM_READ, &pProperties);
+    if (FAILED(hr)) {
+        return hr;
+    }
+    PROPVARIANT varName;
+    PropVariantInit(&varName);
+    hr = pProperties->GetValue(PKEY_Device_FriendlyName, &varName);
+    pProperties->Release();
+    if (FAILED(hr)) {
+        return hr;
+    }
+    if (varName.vt == VT_LPWSTR) {
+        lstrcpynW(pszName, varName.pwszVal, cchName);
+    } else {
+        lstrcpynW(pszName, L"", cchName);
+    }
+    PropVariantClear(&varName);
+
+    return S_OK;
+}
+
+// Gets all active render or capture endpoints
+static HRESULT GetActiveDevices(EDataFlow dataFlow, IMMDeviceCollection** ppDevices)
+{
+    HRESULT hr = S_OK;
+    *ppDevices = NULL;
+
     IMMDeviceEnumerator* pDeviceEnumerator = NULL;
     hr = CoCreateInstance(__uuidof(MMDeviceEnumerator), NULL, CLSCTX_ALL, __uuidof(IMMDeviceEnumerator), (void**)& pDeviceEnumerator);
-    ASSERT_HR(hr);
-    IMMDevice* pDevice = NULL;
-    hr = pDeviceEnumerator->GetDefaultAudioEndpoint(eRender, eMultimedia, &pDevice);
+    if (FAILED(hr)) {
+        return hr;
+    }
+    hr = pDeviceEnumerator->EnumAudioEndpoints(dataFlow, DEVICE_STATE_ACTIVE, ppDevices);
     pDeviceEnumerator->Release();
-    ASSERT_HR(hr);
+
+    return hr;
+}
+
+// Gets the audio session enumerator of an endpoint
+static HRESULT GetSessionEnumerator(IMMDevice* pDevice, IAudioSessionEnumerator** ppSessionEnumerator)
+{
+    HRESULT hr = S_OK;
+    *ppSessionEnumerator = NULL;
 
     // Get the audio session manager for the endpoint
     IAudioSessionManager2* pManager = NULL;
     hr = pDevice->Activate(__uuidof(IAudioSessionManager2), CLSCTX_ALL, NULL, (void**)(&pManager));
-    pDevice->Release();
-    ASSERT_HR(hr);
+    if (FAILED(hr)) {
+        return hr;
+    }
 
     // Get the audio session enumerator for the audio session manager
-    IAudioSessionEnumerator* pSessionEnumerator = NULL;
-    hr = pManager->GetSessionEnumerator(&pSessionEnumerator);
+    hr = pManager->GetSessionEnumerator(ppSessionEnumerator);
     pManager->Release();
-    ASSERT_HR(hr);
+
+    return hr;
+}
+
+// Finds the volume control of the audio session of a process on an endpoint
+static HRESULT GetProcessSessionVolume(IMMDevice* pDevice, DWORD dwPID, ISimpleAudioVolume** ppVolume)
+{
+    HRESULT hr = S_OK;
+    *ppVolume = NULL;
+
+    IAudioSessionEnumerator* pSessionEnumerator = NULL;
+    hr = GetSessionEnumerator(pDevice, &pSessionEnumerator);
+    if (FAILED(hr)) {
+        return hr;
+    }
 
     // Enumerate the audio sessions and find the one that corresponds to the process
-    IAudioSessionControl2 *pTargetSession = NULL;
     int cSessions;
     hr = pSessionEnumerator->GetCount(&cSessions);
     if (FAILED(hr)) {
         pSessionEnumerator->Release();
-        return FALSE;
+        return hr;
     }
     for (int i = 0; i < cSessions; i++) {
         IAudioSessionControl *pControl = NULL;
@@ -54,29 +160,649 @@ BOOL SetProcessMute(DWORD dwPID, BOOL bMute)
             continue;
         }
         IAudioSessionControl2 *pCurrentSession = NULL;
-        pControl->QueryInterface(__uuidof(IAudioSessionControl2), (void**)&pCurrentSession);
+        hr = pControl->QueryInterface(__uuidof(IAudioSessionControl2), (void**)&pCurrentSession);
+        pControl->Release();
+        if (FAILED(hr)) {
+            continue;
+        }
         DWORD dwCurrentPID;
         pCurrentSession->GetProcessId(&dwCurrentPID);
         if (dwCurrentPID == dwPID) {
-            pTargetSession = pCurrentSession;
+            pCurrentSession->QueryInterface(__uuidof(ISimpleAudioVolume), (void**)ppVolume);
+            pCurrentSession->Release();
             break;
         }
         pCurrentSession->Release();
-        pControl->Release();
     }
     pSessionEnumerator->Release();
 
-    // Mute the audio session
-    if (pTargetSession == NULL) {
+    return *ppVolume == NULL ? E_FAIL : S_OK;
+}
+
+// Applies an action to the volume control of a session. Returning S_FALSE stops at this session.
+typedef HRESULT (*SessionVolumeAction)(ISimpleAudioVolume* pVolume, void* pContext);
+
+// Applies an action to the audio sessions of a process on every active render or capture endpoint.
+// Returns the number of endpoints the action succeeded on, and writes their names to pszDevices
+// (one per line) if given.
+static UINT ForEachProcessSessionVolume(DWORD dwPID, EDataFlow dataFlow, SessionVolumeAction action, void* pContext, WCHAR* pszDevices, UINT cchDevices)
+{
+    HRESULT hr = S_OK;
+    UINT cTouched = 0;
+
+    if (pszDevices != NULL && cchDevices > 0) {
+        pszDevices[0] = L'\0';
+    }
+
+    IMMDeviceCollection* pDevices = NULL;
+    hr = GetActiveDevices(dataFlow, &pDevices);
+    if (FAILED(hr)) {
+        return 0;
+    }
+
+    UINT cDevices = 0;
+    pDevices->GetCount(&cDevices);
+    for (UINT i = 0; i < cDevices; i++) {
+        IMMDevice* pDevice = NULL;
+        hr = pDevices->Item(i, &pDevice);
+        if (FAILED(hr)) {
+            continue;
+        }
+        ISimpleAudioVolume* pVolume = NULL;
+        hr = GetProcessSessionVolume(pDevice, dwPID, &pVolume);
+        if (FAILED(hr)) {
+            pDevice->Release();
+            continue;
+        }
+        hr = action(pVolume, pContext);
+        pVolume->Release();
+        if (SUCCEEDED(hr)) {
+            cTouched++;
+
+            // Report the device
+            if (pszDevices != NULL) {
+                WCHAR szId[256];
+                WCHAR szName[256];
+                UINT cchUsed = lstrlenW(pszDevices);
+                if (SUCCEEDED(GetDeviceInfo(pDevice, szId, 256, szName, 256)) && cchUsed + lstrlenW(szName) + 2 <= cchDevices) {
+                    StringCchCatW(pszDevices, cchDevices, szName);
+                    StringCchCatW(pszDevices, cchDevices, L"\n");
+                }
+            }
+        }
+        pDevice->Release();
+        if (hr == S_FALSE) {
+            break;
+        }
+    }
+    pDevices->Release();
+
+    return cTouched;
+}
+
+static HRESULT SetMuteAction(ISimpleAudioVolume* pVolume, void* pContext)
+{
+    return pVolume->SetMute(*(BOOL*)pContext, NULL);
+}
+
+static HRESULT GetMuteAction(ISimpleAudioVolume* pVolume, void* pContext)
+{
+    HRESULT hr = pVolume->GetMute((BOOL*)pContext);
+
+    // The first session is enough
+    return SUCCEEDED(hr) ? S_FALSE : hr;
+}
+
+static HRESULT GetVolumeAction(ISimpleAudioVolume* pVolume, void* pContext)
+{
+    HRESULT hr = pVolume->GetMasterVolume((float*)pContext);
+
+    // The first session is enough
+    return SUCCEEDED(hr) ? S_FALSE : hr;
+}
+
+static HRESULT SetVolumeAction(ISimpleAudioVolume* pVolume, void* pContext)
+{
+    return pVolume->SetMasterVolume(*(float*)pContext, NULL);
+}
+
+UINT SetProcessMute(DWORD dwPID, BOOL bMute, WCHAR* pszDevices, UINT cchDevices)
+{
+    CO_INITIALIZE();
+
+    // Mute the audio sessions on all devices
+    UINT cTouched = ForEachProcessSessionVolume(dwPID, eRender, SetMuteAction, &bMute, pszDevices, cchDevices);
+
+    CO_UNINITIALIZE();
+    return cTouched;
+}
+
+BOOL GetProcessMute(DWORD dwPID, BOOL bCapture, BOOL* pbMuted)
+{
+    CO_INITIALIZE();
+
+    UINT cTouched = ForEachProcessSessionVolume(dwPID, bCapture ? eCapture : eRender, GetMuteAction, pbMuted, NULL, 0);
+
+    CO_UNINITIALIZE();
+    return cTouched > 0;
+}
+
+BOOL SetProcessCaptureMute(DWORD dwPID, BOOL bMute)
+{
+    CO_INITIALIZE();
+
+    // Mute the recording sessions on all devices
+    UINT cTouched = ForEachProcessSessionVolume(dwPID, eCapture, SetMuteAction, &bMute, NULL, 0);
+
+    CO_UNINITIALIZE();
+    return cTouched > 0;
+}
+
+BOOL GetProcessVolume(DWORD dwPID, float* pfLevel)
+{
+    CO_INITIALIZE();
+
+    UINT cTouched = ForEachProcessSessionVolume(dwPID, eRender, GetVolumeAction, pfLevel, NULL, 0);
+
+    CO_UNINITIALIZE();
+    return cTouched > 0;
+}
+
+BOOL SetProcessVolume(DWORD dwPID, float fLevel)
+{
+    CO_INITIALIZE();
+
+    UINT cTouched = ForEachProcessSessionVolume(dwPID, eRender, SetVolumeAction, &fLevel, NULL, 0);
+
+    CO_UNINITIALIZE();
+    return cTouched > 0;
+}
+
+// Calls back whenever an output device is added, removed, enabled, disabled or becomes the default
+class DeviceNotificationClient : public IMMNotificationClient
+{
+public:
+    DeviceNotificationClient(DeviceChangeCallback callback) : m_cRef(1), m_callback(callback) {}
+
+    ULONG STDMETHODCALLTYPE AddRef()
+    {
+        return InterlockedIncrement(&m_cRef);
+    }
+
+    ULONG STDMETHODCALLTYPE Release()
+    {
+        ULONG ulRef = InterlockedDecrement(&m_cRef);
+        if (ulRef == 0) {
+            delete this;
+        }
+        return ulRef;
+    }
+
+    HRESULT STDMETHODCALLTYPE QueryInterface(REFIID riid, void** ppvInterface)
+    {
+        if (riid == IID_IUnknown || riid == __uuidof(IMMNotificationClient)) {
+            *ppvInterface = (IMMNotificationClient*)this;
+            AddRef();
+            return S_OK;
+        }
+        *ppvInterface = NULL;
+        return E_NOINTERFACE;
+    }
+
+    HRESULT STDMETHODCALLTYPE OnDefaultDeviceChanged(EDataFlow flow, ERole, LPCWSTR)
+    {
+        if (flow == eRender) {
+            m_callback();
+        }
+        return S_OK;
+    }
+
+    HRESULT STDMETHODCALLTYPE OnDeviceAdded(LPCWSTR)
+    {
+        m_callback();
+        return S_OK;
+    }
+
+    HRESULT STDMETHODCALLTYPE OnDeviceRemoved(LPCWSTR)
+    {
+        m_callback();
+        return S_OK;
+    }
+
+    HRESULT STDMETHODCALLTYPE OnDeviceStateChanged(LPCWSTR, DWORD)
+    {
+        m_callback();
+        return S_OK;
+    }
+
+    HRESULT STDMETHODCALLTYPE OnPropertyValueChanged(LPCWSTR, const PROPERTYKEY)
+    {
+        return S_OK;
+    }
+
+private:
+    LONG m_cRef;
+    DeviceChangeCallback m_callback;
+};
+
+static IMMDeviceEnumerator* g_pNotificationEnumerator = NULL;
+static DeviceNotificationClient* g_pNotificationClient = NULL;
+static BOOL g_bNotificationCoInit = FALSE;
+
+BOOL RegisterDeviceChangeCallback(DeviceChangeCallback callback)
+{
+    CO_INITIALIZE();
+    HRESULT hr = S_OK;
+
+    // The enumerator has to stay alive for as long as notifications are wanted
+    hr = CoCreateInstance(__uuidof(MMDeviceEnumerator), NULL, CLSCTX_ALL, __uuidof(IMMDeviceEnumerator), (void**)& g_pNotificationEnumerator);
+    ASSERT_HR(hr);
+    g_pNotificationClient = new DeviceNotificationClient(callback);
+    hr = g_pNotificationEnumerator->RegisterEndpointNotificationCallback(g_pNotificationClient);
+    if (FAILED(hr)) {
+        g_pNotificationClient->Release();
+        g_pNotificationClient = NULL;
+        g_pNotificationEnumerator->Release();
+        g_pNotificationEnumerator = NULL;
+        CO_UNINITIALIZE();
         return FALSE;
     }
-    ISimpleAudioVolume *pVolume = NULL;
-    hr = pTargetSession->QueryInterface(__uuidof(ISimpleAudioVolume), (void**)&pVolume);
-    pTargetSession->Release();
+
+    // COM stays initialized until the callback is unregistered
+    g_bNotificationCoInit = SUCCEEDED(hrCoInit);
+    return TRUE;
+}
+
+void UnregisterDeviceChangeCallback()
+{
+    if (g_pNotificationEnumerator == NULL) {
+        return;
+    }
+
+    g_pNotificationEnumerator->UnregisterEndpointNotificationCallback(g_pNotificationClient);
+    g_pNotificationClient->Release();
+    g_pNotificationClient = NULL;
+    g_pNotificationEnumerator->Release();
+    g_pNotificationEnumerator = NULL;
+    if (g_bNotificationCoInit) {
+        CoUninitialize();
+        g_bNotificationCoInit = FALSE;
+    }
+}
+
+// Calls back with the process ID whenever a new audio session is created
+class SessionNotificationClient : public IAudioSessionNotification
+{
+public:
+    SessionNotificationClient(SessionCreatedCallback callback) : m_cRef(1), m_callback(callback) {}
+
+    ULONG STDMETHODCALLTYPE AddRef()
+    {
+        return InterlockedIncrement(&m_cRef);
+    }
+
+    ULONG STDMETHODCALLTYPE Release()
+    {
+        ULONG ulRef = InterlockedDecrement(&m_cRef);
+        if (ulRef == 0) {
+            delete this;
+        }
+        return ulRef;
+    }
+
+    HRESULT STDMETHODCALLTYPE QueryInterface(REFIID riid, void** ppvInterface)
+    {
+        if (riid == IID_IUnknown || riid == __uuidof(IAudioSessionNotification)) {
+            *ppvInterface = (IAudioSessionNotification*)this;
+            AddRef();
+            return S_OK;
+        }
+        *ppvInterface = NULL;
+        return E_NOINTERFACE;
+    }
+
+    HRESULT STDMETHODCALLTYPE OnSessionCreated(IAudioSessionControl* pNewSession)
+    {
+        IAudioSessionControl2* pSession = NULL;
+        HRESULT hr = pNewSession->QueryInterface(__uuidof(IAudioSessionControl2), (void**)&pSession);
+        if (SUCCEEDED(hr)) {
+            DWORD dwPID = 0;
+            pSession->GetProcessId(&dwPID);
+            pSession->Release();
+            m_callback(dwPID);
+        }
+        return S_OK;
+    }
+
+private:
+    LONG m_cRef;
+    SessionCreatedCallback m_callback;
+};
+
+#define MAX_SESSION_MANAGERS 32
+
+static IAudioSessionManager2* g_pSessionManagers[MAX_SESSION_MANAGERS];
+static UINT g_cSessionManagers = 0;
+static SessionNotificationClient* g_pSessionClient = NULL;
+static BOOL g_bSessionCoInit = FALSE;
+
+BOOL RegisterSessionCreatedCallback(SessionCreatedCallback callback)
+{
+    CO_INITIALIZE();
+    HRESULT hr = S_OK;
+
+    IMMDeviceCollection* pDevices = NULL;
+    hr = GetActiveDevices(eRender, &pDevices);
+    ASSERT_HR(hr);
+
+    // Register on every output device, since new sessions are created per device
+    g_pSessionClient = new SessionNotificationClient(callback);
+    UINT cDevices = 0;
+    pDevices->GetCount(&cDevices);
+    for (UINT i = 0; i < cDevices && g_cSessionManagers < MAX_SESSION_MANAGERS; i++) {
+        IMMDevice* pDevice = NULL;
+        hr = pDevices->Item(i, &pDevice);
+        if (FAILED(hr)) {
+            continue;
+        }
+        IAudioSessionManager2* pManager = NULL;
+        hr = pDevice->Activate(__uuidof(IAudioSessionManager2), CLSCTX_ALL, NULL, (void**)(&pManager));
+        pDevice->Release();
+        if (FAILED(hr)) {
+            continue;
+        }
+
+        // Notifications only arrive once the session enumerator has been requested
+        IAudioSessionEnumerator* pSessionEnumerator = NULL;
+        hr = pManager->GetSessionEnumerator(&pSessionEnumerator);
+        if (SUCCEEDED(hr)) {
+            pSessionEnumerator->Release();
+            hr = pManager->RegisterSessionNotification(g_pSessionClient);
+        }
+        if (FAILED(hr)) {
+            pManager->Release();
+            continue;
+        }
+        g_pSessionManagers[g_cSessionManagers++] = pManager;
+    }
+    pDevices->Release();
+
+    // COM stays initialized until the callback is unregistered
+    g_bSessionCoInit = SUCCEEDED(hrCoInit);
+    return TRUE;
+}
+
+void UnregisterSessionCreatedCallback()
+{
+    if (g_pSessionClient == NULL) {
+        return;
+    }
+
+    for (UINT i = 0; i < g_cSessionManagers; i++) {
+        g_pSessionManagers[i]->UnregisterSessionNotification(g_pSessionClient);
+        g_pSessionManagers[i]->Release();
+    }
+    g_cSessionManagers = 0;
+    g_pSessionClient->Release();
+    g_pSessionClient = NULL;
+    if (g_bSessionCoInit) {
+        CoUninitialize();
+        g_bSessionCoInit = FALSE;
+    }
+}
+
+BOOL GetDefaultRenderDevice(WCHAR* pszId, UINT cchId, WCHAR* pszName, UINT cchName)
+{
+    CO_INITIALIZE();
+    HRESULT hr = S_OK;
+
+    // Get the default audio endpoint
+    IMMDeviceEnumerator* pDeviceEnumerator = NULL;
+    hr = CoCreateInstance(__uuidof(MMDeviceEnumerator), NULL, CLSCTX_ALL, __uuidof(IMMDeviceEnumerator), (void**)& pDeviceEnumerator);
+    ASSERT_HR(hr);
+    IMMDevice* pDevice = NULL;
+    hr = pDeviceEnumerator->GetDefaultAudioEndpoint(eRender, eMultimedia, &pDevice);
+    pDeviceEnumerator->Release();
+    ASSERT_HR(hr);
+
+    hr = GetDeviceInfo(pDevice, pszId, cchId, pszName, cchName);
+    pDevice->Release();
+    ASSERT_HR(hr);
+
+    CO_UNINITIALIZE();
+    return TRUE;
+}
+
+UINT GetRenderDevices(WCHAR* pszIds, WCHAR* pszNames, UINT cchEach, UINT cMaxDevices)
+{
+    CO_INITIALIZE();
+    HRESULT hr = S_OK;
+    UINT cFound = 0;
+
+    IMMDeviceCollection* pDevices = NULL;
+    hr = GetActiveDevices(eRender, &pDevices);
+    if (FAILED(hr)) {
+        CO_UNINITIALIZE();
+        return 0;
+    }
+
+    UINT cDevices = 0;
+    pDevices->GetCount(&cDevices);
+    for (UINT i = 0; i < cDevices && cFound < cMaxDevices; i++) {
+        IMMDevice* pDevice = NULL;
+        hr = pDevices->Item(i, &pDevice);
+        if (FAILED(hr)) {
+            continue;
+        }
+        hr = GetDeviceInfo(pDevice, pszIds + cFound * cchEach, cchEach, pszNames + cFound * cchEach, cchEach);
+        pDevice->Release();
+        if (SUCCEEDED(hr)) {
+            cFound++;
+        }
+    }
+    pDevices->Release();
+    CO_UNINITIALIZE();
+
+    return cFound;
+}
+
+BOOL SetProcessOutputDevice(DWORD dwPID, const WCHAR* pszDeviceId)
+{
+    CO_INITIALIZE();
+    HRESULT hr = S_OK;
+
+    HSTRING hClassName = NULL;
+    hr = WindowsCreateString(AUDIO_POLICY_CONFIG_CLASS, (UINT32)wcslen(AUDIO_POLICY_CONFIG_CLASS), &hClassName);
+    ASSERT_HR(hr);
+    IAudioPolicyConfigFactory* pPolicyConfig = NULL;
+    hr = RoGetActivationFactory(hClassName, IID_IAudioPolicyConfigFactory, (void**)&pPolicyConfig);
+    if (FAILED(hr)) {
+        hr = RoGetActivationFactory(hClassName, IID_IAudioPolicyConfigFactoryLegacy, (void**)&pPolicyConfig);
+    }
+    WindowsDeleteString(hClassName);
     ASSERT_HR(hr);
-    pVolume->SetMute(bMute, NULL);
-    pVolume->Release();
+
+    // A null device ID resets the process to the default device
+    HSTRING hDeviceId = NULL;
+    if (pszDeviceId != NULL) {
+        // Fails with STRSAFE_E_INSUFFICIENT_BUFFER rather than passing on a truncated ID
+        WCHAR szFullId[512];
+        hr = StringCchPrintfW(szFullId, ARRAYSIZE(szFullId), L"%s%s%s", MMDEVAPI_PREFIX, pszDeviceId, MMDEVAPI_RENDER_SUFFIX);
+        if (SUCCEEDED(hr)) {
+            hr = WindowsCreateString(szFullId, (UINT32)wcslen(szFullId), &hDeviceId);
+        }
+        if (FAILED(hr)) {
+            pPolicyConfig->Release();
+            CO_UNINITIALIZE();
+            return FALSE;
+        }
+    }
+
+    hr = pPolicyConfig->SetPersistedDefaultAudioEndpoint(dwPID, eRender, eMultimedia, hDeviceId);
+    if (SUCCEEDED(hr)) {
+        hr = pPolicyConfig->SetPersistedDefaultAudioEndpoint(dwPID, eRender, eConsole, hDeviceId);
+    }
+    WindowsDeleteString(hDeviceId);
+    pPolicyConfig->Release();
     ASSERT_HR(hr);
 
+    CO_UNINITIALIZE();
     return TRUE;
 }
+
+UINT GetAudioSessions(AudioSessionInfo* pSessions, UINT cMaxSessions)
+{
+    CO_INITIALIZE();
+    HRESULT hr = S_OK;
+    UINT cFound = 0;
+
+    IMMDeviceCollection* pDevices = NULL;
+    hr = GetActiveDevices(eRender, &pDevices);
+    if (FAILED(hr)) {
+        CO_UNINITIALIZE();
+        return 0;
+    }
+
+    UINT cDevices = 0;
+    pDevices->GetCount(&cDevices);
+    for (UINT iDevice = 0; iDevice < cDevices && cFound < cMaxSessions; iDevice++) {
+        IMMDevice* pDevice = NULL;
+        hr = pDevices->Item(iDevice, &pDevice);
+        if (FAILED(hr)) {
+            continue;
+        }
+        WCHAR szDeviceId[SESSION_STRING_LEN];
+        WCHAR szDeviceName[SESSION_STRING_LEN];
+        hr = GetDeviceInfo(pDevice, szDeviceId, SESSION_STRING_LEN, szDeviceName, SESSION_STRING_LEN);
+        if (FAILED(hr)) {
+            lstrcpynW(szDeviceName, L"", SESSION_STRING_LEN);
+        }
+        IAudioSessionEnumerator* pSessionEnumerator = NULL;
+        hr = GetSessionEnumerator(pDevice, &pSessionEnumerator);
+        pDevice->Release();
+        if (FAILED(hr)) {
+            continue;
+        }
+
+        int cSessions = 0;
+        pSessionEnumerator->GetCount(&cSessions);
+        for (int i = 0; i < cSessions && cFound < cMaxSessions; i++) {
+            IAudioSessionControl *pControl = NULL;
+            hr = pSessionEnumerator->GetSession(i, &pControl);
+            if (FAILED(hr)) {
+                continue;
+            }
+            IAudioSessionControl2 *pSession = NULL;
+            hr = pControl->QueryInterface(__uuidof(IAudioSessionControl2), (void**)&pSession);
+            pControl->Release();
+            if (FAILED(hr)) {
+                continue;
+            }
+
+            AudioSessionInfo* pInfo = &pSessions[cFound];
+            pInfo->dwPID = 0;
+            pInfo->bMuted = FALSE;
+            pInfo->fVolume = 1.0f;
+            pSession->GetProcessId(&pInfo->dwPID);
+            lstrcpynW(pInfo->szDevice, szDeviceName, SESSION_STRING_LEN);
+
+            // Most apps don't set a display name
+            LPWSTR pszDisplayName = NULL;
+            hr = pSession->GetDisplayName(&pszDisplayName);
+            if (SUCCEEDED(hr) && pszDisplayName != NULL) {
+                lstrcpynW(pInfo->szDisplayName, pszDisplayName, SESSION_STRING_LEN);
+                CoTaskMemFree(pszDisplayName);
+            } else {
+                lstrcpynW(pInfo->szDisplayName, L"", SESSION_STRING_LEN);
+            }
+
+            ISimpleAudioVolume* pVolume = NULL;
+            hr = pSession->QueryInterface(__uuidof(ISimpleAudioVolume), (void**)&pVolume);
+            if (SUCCEEDED(hr)) {
+                pVolume->GetMute(&pInfo->bMuted);
+                pVolume->GetMasterVolume(&pInfo->fVolume);
+                pVolume->Release();
+            }
+            pSession->Release();
+
+            cFound++;
+        }
+        pSessionEnumerator->Release();
+    }
+    pDevices->Release();
+    CO_UNINITIALIZE();
+
+    return cFound;
+}
+
+UINT GetAudioSessionActivity(BOOL bCapture, DWORD* pPIDs, BOOL* pActive, float* pPeaks, UINT cMaxSessions)
+{
+    CO_INITIALIZE();
+    HRESULT hr = S_OK;
+    UINT cFound = 0;
+
+    IMMDeviceCollection* pDevices = NULL;
+    hr = GetActiveDevices(bCapture ? eCapture : eRender, &pDevices);
+    if (FAILED(hr)) {
+        CO_UNINITIALIZE();
+        return 0;
+    }
+
+    UINT cDevices = 0;
+    pDevices->GetCount(&cDevices);
+    for (UINT iDevice = 0; iDevice < cDevices && cFound < cMaxSessions; iDevice++) {
+        IMMDevice* pDevice = NULL;
+        hr = pDevices->Item(iDevice, &pDevice);
+        if (FAILED(hr)) {
+            continue;
+        }
+        IAudioSessionEnumerator* pSessionEnumerator = NULL;
+        hr = GetSessionEnumerator(pDevice, &pSessionEnumerator);
+        pDevice->Release();
+        if (FAILED(hr)) {
+            continue;
+        }
+
+        int cSessions = 0;
+        pSessionEnumerator->GetCount(&cSessions);
+        for (int i = 0; i < cSessions && cFound < cMaxSessions; i++) {
+            IAudioSessionControl *pControl = NULL;
+            hr = pSessionEnumerator->GetSession(i, &pControl);
+            if (FAILED(hr)) {
+                continue;
+            }
+            IAudioSessionControl2 *pSession = NULL;
+            hr = pControl->QueryInterface(__uuidof(IAudioSessionControl2), (void**)&pSession);
+            pControl->Release();
+            if (FAILED(hr)) {
+                continue;
+            }
+
+            // Session state: active while the process has a running stream
+            DWORD dwPID = 0;
+            AudioSessionState state = AudioSessionStateInactive;
+            pSession->GetProcessId(&dwPID);
+            pSession->GetState(&state);
+
+            // Peak level of the session's audio over the last period
+            float fPeak = 0.0f;
+            IAudioMeterInformation *pMeter = NULL;
+            hr = pSession->QueryInterface(__uuidof(IAudioMeterInformation), (void**)&pMeter);
+            if (SUCCEEDED(hr)) {
+                pMeter->GetPeakValue(&fPeak);
+                pMeter->Release();
+            }
+            pSession->Release();
+
+            pPIDs[cFound] = dwPID;
+            pActive[cFound] = state == AudioSessionStateActive;
+            pPeaks[cFound] = fPeak;
+            cFound++;
+        }
+        pSessionEnumerator->Release();
+    }
+    pDevices->Release();
+    CO_UNINITIALIZE();
+
+    return cFound;
+}
@@ -2,4 +2,30 @@
 
 #include <windef.h>
 
-extern "C" BOOL SetProcessMute(DWORD dwPID, BOOL bMute);
+#define SESSION_STRING_LEN 256
+
+struct AudioSessionInfo
+{
+    DWORD dwPID;
+    BOOL bMuted;
+    float fVolume;
+    WCHAR szDisplayName[SESSION_STRING_LEN];
+    WCHAR szDevice[SESSION_STRING_LEN];
+};
+
+extern "C" UINT SetProcessMute(DWORD dwPID, BOOL bMute, WCHAR* pszDevices, UINT cchDevices);
+extern "C" BOOL GetProcessMute(DWORD dwPID, BOOL bCapture, BOOL* pbMuted);
+extern "C" BOOL SetProcessCaptureMute(DWORD dwPID, BOOL bMute);
+extern "C" BOOL GetProcessVolume(DWORD dwPID, float* pfLevel);
+extern "C" BOOL SetProcessVolume(DWORD dwPID, float fLevel);
+extern "C" BOOL GetDefaultRenderDevice(WCHAR* pszId, UINT cchId, WCHAR* pszName, UINT cchName);
+extern "C" UINT GetRenderDevices(WCHAR* pszIds, WCHAR* pszNames, UINT cchEach, UINT cMaxDevices);
+extern "C" BOOL SetProcessOutputDevice(DWORD dwPID, const WCHAR* pszDeviceId);
+typedef void (*DeviceChangeCallback)();
+extern "C" BOOL RegisterDeviceChangeCallback(DeviceChangeCallback callback);
+extern "C" void UnregisterDeviceChangeCallback();
+typedef void (*SessionCreatedCallback)(DWORD dwPID);
+extern "C" BOOL RegisterSessionCreatedCallback(SessionCreatedCallback callback);
+extern "C" void UnregisterSessionCreatedCallback();
+extern "C" UINT GetAudioSessions(AudioSessionInfo* pSessions, UINT cMaxSessions);
+extern "C" UINT GetAudioSessionActivity(BOOL bCapture, DWORD* pPIDs, BOOL* pActive, float* pPeaks, UINT cMaxSessions);
@@ -1,31 +1,497 @@
 use std::{
-    collections::HashSet,
-    mem::{self, MaybeUninit},
+    collections::{HashMap, HashSet},
+    iter, mem,
+    path::PathBuf,
+    ptr,
     sync::mpsc::{self, Receiver, Sender},
     thread::{self, JoinHandle},
-    time::{Duration, SystemTime},
 };
 
-use comedy::Win32Error;
+use itertools::Itertools;
 use log::{debug, info, warn};
+use unicase::UniCase;
 use winapi::{
-    shared::minwindef::{BOOL, DWORD, FALSE, FILETIME},
-    um::{
-        handleapi::CloseHandle,
-        processthreadsapi::{GetProcessTimes, OpenProcess},
-        winnt::PROCESS_QUERY_LIMITED_INFORMATION,
-    },
+    shared::minwindef::{BOOL, DWORD, FALSE, UINT},
+    um::winnt::WCHAR,
+};
+
+use crate::{
+    core::ProgramPath,
+    mute_journal::{JournalEntry, JournalFile, MuteJournal},
+    process::{self, ProcessId},
 };
 
 extern "C" {
-    fn SetProcessMute(dwPID: DWORD, bMute: BOOL) -> BOOL;
+    fn SetProcessMute(dwPID: DWORD, bMute: BOOL, pszDevices: *mut WCHAR, cchDevices: UINT) -> UINT;
+    fn GetProcessMute(dwPID: DWORD, bCapture: BOOL, pbMuted: *mut BOOL) -> BOOL;
+    fn SetProcessCaptureMute(dwPID: DWORD, bMute: BOOL) -> BOOL;
+    fn GetProcessVolume(dwPID: DWORD, pfLevel: *mut f32) -> BOOL;
+    fn SetProcessVolume(dwPID: DWORD, fLevel: f32) -> BOOL;
+    fn GetDefaultRenderDevice(
+        pszId: *mut WCHAR,
+        cchId: UINT,
+        pszName: *mut WCHAR,
+        cchName: UINT,
+    ) -> BOOL;
+    fn GetRenderDevices(
+        pszIds: *mut WCHAR,
+        pszNames: *mut WCHAR,
+        cchEach: UINT,
+        cMaxDevices: UINT,
+    ) -> UINT;
+    fn SetProcessOutputDevice(dwPID: DWORD, pszDeviceId: *const WCHAR) -> BOOL;
+    fn GetAudioSessions(pSessions: *mut AudioSessionInfo, cMaxSessions: UINT) -> UINT;
+}
+
+const MAX_DEVICES: usize = 32;
+const DEVICE_STRING_LEN: usize = 256;
+const MAX_SESSIONS: usize = 256;
+const SESSION_STRING_LEN: usize = 256;
+
+// mirrors AudioSessionInfo in mute_control.hpp
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct AudioSessionInfo {
+    pid: DWORD,
+    muted: BOOL,
+    volume: f32,
+    display_name: [WCHAR; SESSION_STRING_LEN],
+    device: [WCHAR; SESSION_STRING_LEN],
+}
+
+/// An audio session of a process on an output device.
+#[derive(Clone, Debug)]
+pub struct AudioSession {
+    pub pid: DWORD,
+    pub program_path: Option<ProgramPath>,
+    pub display_name: String,
+    pub muted: bool,
+    pub volume: f32,
+    pub device: String,
+}
+
+/// Lists the audio sessions of all processes on all active output devices.
+pub fn get_audio_sessions() -> Vec<AudioSession> {
+    let mut sessions = vec![unsafe { mem::zeroed::<AudioSessionInfo>() }; MAX_SESSIONS];
+
+    let session_count =
+        unsafe { GetAudioSessions(sessions.as_mut_ptr(), MAX_SESSIONS as _) } as usize;
+
+    sessions
+        .iter()
+        .take(session_count)
+        .map(|session| AudioSession {
+            pid: session.pid,
+            program_path: process::get_path_from_pid(session.pid),
+            display_name: from_wide(&session.display_name),
+            muted: session.muted != FALSE,
+            volume: session.volume,
+            device: from_wide(&session.device),
+        })
+        .collect()
+}
+
+fn from_wide(buf: &[WCHAR]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    String::from_utf16_lossy(&buf[..len])
+}
+
+/// An active output device.
+#[derive(Clone, Debug)]
+pub struct OutputDevice {
+    pub id: String,
+    pub name: String,
+}
+
+/// Lists the active output devices.
+pub fn get_output_devices() -> Vec<OutputDevice> {
+    let mut ids = vec![0 as WCHAR; MAX_DEVICES * DEVICE_STRING_LEN];
+    let mut names = vec![0 as WCHAR; MAX_DEVICES * DEVICE_STRING_LEN];
+
+    let device_count = unsafe {
+        GetRenderDevices(
+            ids.as_mut_ptr(),
+            names.as_mut_ptr(),
+            DEVICE_STRING_LEN as _,
+            MAX_DEVICES as _,
+        )
+    } as usize;
+
+    ids.chunks(DEVICE_STRING_LEN)
+        .zip(names.chunks(DEVICE_STRING_LEN))
+        .take(device_count)
+        .map(|(id, name)| OutputDevice {
+            id: from_wide(id),
+            name: from_wide(name),
+        })
+        .collect()
+}
+
+/// The output device that apps play through by default.
+pub fn get_default_output_device() -> Option<OutputDevice> {
+    let mut id = [0 as WCHAR; DEVICE_STRING_LEN];
+    let mut name = [0 as WCHAR; DEVICE_STRING_LEN];
+
+    let ok = unsafe {
+        GetDefaultRenderDevice(
+            id.as_mut_ptr(),
+            id.len() as _,
+            name.as_mut_ptr(),
+            name.len() as _,
+        )
+    };
+
+    if ok == FALSE {
+        return None;
+    }
+
+    Some(OutputDevice {
+        id: from_wide(&id),
+        name: from_wide(&name),
+    })
+}
+
+/// Finds the output device whose name contains `device_name` (case-insensitive).
+pub fn find_output_device(device_name: &str) -> Option<OutputDevice> {
+    if device_name.is_empty() {
+        return None;
+    }
+
+    let device_name = device_name.to_lowercase();
+
+    get_output_devices()
+        .into_iter()
+        .find(|device| device.name.to_lowercase().contains(&device_name))
 }
 
 #[derive(Debug)]
 enum MuteProxyMessage {
-    Mute(DWORD),
-    Unmute(DWORD, bool),
-    UnmuteFollowup(DWORD, SystemTime),
+    Mute(ProcessId),
+    Duck(ProcessId, f32),
+    Reroute(ProcessId, String),
+    MuteCapture(ProcessId),
+    Unmute(ProcessId),
+    SessionCreated(ProcessId),
+    ForceUnmute(ProcessId),
+    RestoreAll,
+    ProcessStarted(ProcessId),
+    ProcessExited(ProcessId),
+}
+
+impl MuteProxyMessage {
+    fn get_process(&self) -> Option<ProcessId> {
+        match *self {
+            MuteProxyMessage::Mute(process)
+            | MuteProxyMessage::Duck(process, _)
+            | MuteProxyMessage::Reroute(process, _)
+            | MuteProxyMessage::MuteCapture(process)
+            | MuteProxyMessage::Unmute(process)
+            | MuteProxyMessage::SessionCreated(process)
+            | MuteProxyMessage::ForceUnmute(process)
+            | MuteProxyMessage::ProcessStarted(process) => Some(process),
+            MuteProxyMessage::RestoreAll | MuteProxyMessage::ProcessExited(_) => None,
+        }
+    }
+}
+
+/// Everything the proxy has changed about processes, so that exactly that can be undone. Mutes
+/// the user applied by hand are left alone.
+#[derive(Default)]
+struct AppliedChanges {
+    mutes_before: HashMap<ProcessId, bool>,
+    volumes_before_duck: HashMap<ProcessId, f32>,
+    rerouted_processes: HashSet<ProcessId>,
+    capture_mutes_before: HashMap<ProcessId, bool>,
+    /// The program of every changed process, since some changes outlive the process.
+    program_paths: HashMap<ProcessId, ProgramPath>,
+    /// Programs whose lasting output device setting still has to be cleared, since their rerouted
+    /// process exited before it was moved back. Windows only clears it through a running process.
+    endpoint_resets: HashSet<ProgramPath>,
+    /// Programs whose process exited while muted by annie. Windows gives their next session that
+    /// mute state again.
+    mute_resets: HashSet<ProgramPath>,
+}
+
+impl AppliedChanges {
+    /// Takes over the journaled changes. Those of processes that are gone are undone for their
+    /// program, like when forgetting a process.
+    fn from_journal_file(journal_file: JournalFile) -> Self {
+        let to_program_paths = |program_paths: Vec<String>| {
+            program_paths
+                .into_iter()
+                .map(|program_path| UniCase::new(program_path.into()))
+                .collect()
+        };
+
+        let mut changes = AppliedChanges {
+            endpoint_resets: to_program_paths(journal_file.endpoint_resets),
+            mute_resets: to_program_paths(journal_file.mute_resets),
+            ..Default::default()
+        };
+
+        for entry in journal_file.entries {
+            let process = ProcessId {
+                pid: entry.pid,
+                start_time: entry.start_time,
+            };
+
+            changes
+                .program_paths
+                .insert(process, UniCase::new(entry.program_path.into()));
+            if let Some(muted_before) = entry.muted_before {
+                changes.mutes_before.insert(process, muted_before);
+            }
+            if let Some(volume_before) = entry.volume_before {
+                changes.volumes_before_duck.insert(process, volume_before);
+            }
+            if entry.rerouted {
+                changes.rerouted_processes.insert(process);
+            }
+            if let Some(capture_muted_before) = entry.capture_muted_before {
+                changes
+                    .capture_mutes_before
+                    .insert(process, capture_muted_before);
+            }
+        }
+
+        changes
+    }
+
+    /// Records the program of a process about to be changed. Returns false if it has exited.
+    fn track_program(&mut self, process: ProcessId) -> bool {
+        if self.program_paths.contains_key(&process) {
+            return true;
+        }
+
+        let Some(program_path) = process.get_program_path() else { return false };
+        self.program_paths.insert(process, program_path);
+        true
+    }
+
+    fn mute(&mut self, process: ProcessId) {
+        if !self.track_program(process) {
+            return;
+        }
+
+        // keep the state from before the first mute
+        if !self.mutes_before.contains_key(&process) {
+            if let Some(muted_before) = MuteProxy::get_mute_synchronous(process.pid, false) {
+                self.mutes_before.insert(process, muted_before);
+            }
+        }
+
+        MuteProxy::set_mute_synchronous(process.pid, true);
+    }
+
+    /// Unmutes a new session if it got its mute state from a process that annie left muted.
+    /// Other muted sessions were muted by the user and stay muted.
+    fn reset_mute_of_new_session(&mut self, process: ProcessId) {
+        // the session is muted along with the rest of the process
+        if self.mute_resets.is_empty() || self.mutes_before.contains_key(&process) {
+            return;
+        }
+
+        let Some(program_path) = process.get_program_path() else { return };
+        if self.mute_resets.remove(&program_path) {
+            MuteProxy::set_mute_synchronous(process.pid, false);
+        }
+    }
+
+    fn duck(&mut self, process: ProcessId, volume: f32) {
+        if !self.track_program(process) {
+            return;
+        }
+
+        let volume_before = self
+            .volumes_before_duck
+            .get(&process)
+            .copied()
+            .or_else(|| MuteProxy::get_volume_synchronous(process.pid));
+
+        if let Some(volume_before) = volume_before {
+            self.volumes_before_duck.insert(process, volume_before);
+            MuteProxy::set_volume_synchronous(process.pid, volume);
+        }
+
+        self.restore_mute(process);
+        self.restore_output_device(process);
+        self.restore_capture_mute(process);
+    }
+
+    fn reroute(&mut self, process: ProcessId, device: &OutputDevice) {
+        if !self.track_program(process) {
+            return;
+        }
+
+        self.restore_mute(process);
+        MuteProxy::set_output_device_synchronous(process.pid, Some(device));
+        self.endpoint_resets.remove(&self.program_paths[&process]);
+        self.rerouted_processes.insert(process);
+    }
+
+    fn mute_capture(&mut self, process: ProcessId) {
+        if !self.track_program(process) {
+            return;
+        }
+
+        if !self.capture_mutes_before.contains_key(&process) {
+            if let Some(muted_before) = MuteProxy::get_mute_synchronous(process.pid, true) {
+                self.capture_mutes_before.insert(process, muted_before);
+            }
+        }
+
+        MuteProxy::set_capture_mute_synchronous(process.pid, true);
+    }
+
+    fn restore_mute(&mut self, process: ProcessId) {
+        if let Some(false) = self.mutes_before.remove(&process) {
+            MuteProxy::set_mute_synchronous(process.pid, false);
+        }
+    }
+
+    fn restore_volume(&mut self, process: ProcessId) {
+        if let Some(volume_before) = self.volumes_before_duck.remove(&process) {
+            MuteProxy::set_volume_synchronous(process.pid, volume_before);
+        }
+    }
+
+    fn restore_output_device(&mut self, process: ProcessId) {
+        if self.rerouted_processes.remove(&process) {
+            MuteProxy::set_output_device_synchronous(process.pid, None);
+        }
+    }
+
+    /// Clears the output device setting left behind by exited processes through another running
+    /// process of the same program. Programs without one are cleared once they start again.
+    fn reset_endpoints(&mut self) {
+        if self.endpoint_resets.is_empty() {
+            return;
+        }
+
+        let still_rerouted: HashSet<_> = self
+            .rerouted_processes
+            .iter()
+            .filter_map(|process| self.program_paths.get(process))
+            .cloned()
+            .collect();
+
+        self.endpoint_resets.retain(|program_path| {
+            // cleared once the remaining rerouted process is moved back
+            if still_rerouted.contains(program_path) {
+                return false;
+            }
+
+            match process::get_processes_from_path(program_path).first() {
+                Some(process) => {
+                    MuteProxy::set_output_device_synchronous(process.pid, None);
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+
+    fn reset_endpoint_of_started(&mut self, process: ProcessId) {
+        if self.endpoint_resets.is_empty() {
+            return;
+        }
+
+        let Some(program_path) = process.get_program_path() else { return };
+        if self.endpoint_resets.remove(&program_path) {
+            MuteProxy::set_output_device_synchronous(process.pid, None);
+        }
+    }
+
+    fn restore_capture_mute(&mut self, process: ProcessId) {
+        if let Some(false) = self.capture_mutes_before.remove(&process) {
+            MuteProxy::set_capture_mute_synchronous(process.pid, false);
+        }
+    }
+
+    fn restore(&mut self, process: ProcessId) {
+        self.restore_mute(process);
+        self.restore_volume(process);
+        self.restore_output_device(process);
+        self.restore_capture_mute(process);
+        self.program_paths.remove(&process);
+    }
+
+    fn restore_all(&mut self) {
+        for process in self.get_changed_processes() {
+            // the PID may belong to a different process by now
+            if process.is_running() {
+                self.restore(process);
+            } else {
+                self.forget(process);
+            }
+        }
+    }
+
+    /// Drops everything recorded about a process, without touching it. Its mute state and output
+    /// device setting outlive it though, so these are still undone for its program.
+    fn forget(&mut self, process: ProcessId) {
+        let muted_before = self.mutes_before.remove(&process);
+        let rerouted = self.rerouted_processes.remove(&process);
+        self.volumes_before_duck.remove(&process);
+        self.capture_mutes_before.remove(&process);
+
+        let Some(program_path) = self.program_paths.remove(&process) else { return };
+
+        if muted_before == Some(false) {
+            self.mute_resets.insert(program_path.clone());
+        }
+
+        if rerouted {
+            self.endpoint_resets.insert(program_path);
+            self.reset_endpoints();
+        }
+    }
+
+    fn get_changed_processes(&self) -> HashSet<ProcessId> {
+        self.mutes_before
+            .keys()
+            .chain(self.volumes_before_duck.keys())
+            .chain(self.rerouted_processes.iter())
+            .chain(self.capture_mutes_before.keys())
+            .copied()
+            .collect()
+    }
+
+    fn get_journal_file(&self) -> JournalFile {
+        let mut entries = self
+            .get_changed_processes()
+            .into_iter()
+            .map(|process| JournalEntry {
+                pid: process.pid,
+                start_time: process.start_time,
+                muted_before: self.mutes_before.get(&process).copied(),
+                volume_before: self.volumes_before_duck.get(&process).copied(),
+                program_path: self
+                    .program_paths
+                    .get(&process)
+                    .map(|program_path| program_path.to_string())
+                    .unwrap_or_default(),
+                rerouted: self.rerouted_processes.contains(&process),
+                capture_muted_before: self.capture_mutes_before.get(&process).copied(),
+            })
+            .collect_vec();
+
+        entries.sort_unstable_by_key(|entry| (entry.pid, entry.start_time));
+
+        let to_names = |program_paths: &HashSet<ProgramPath>| {
+            program_paths
+                .iter()
+                .map(|program_path| program_path.to_string())
+                .sorted()
+                .collect()
+        };
+
+        JournalFile {
+            entries,
+            endpoint_resets: to_names(&self.endpoint_resets),
+            mute_resets: to_names(&self.mute_resets),
+        }
+    }
 }
 
 pub struct MuteProxy {
@@ -34,13 +500,9 @@ pub struct MuteProxy {
 }
 
 impl MuteProxy {
-    const PROCESS_AGE_THRESHOLD_MS: u128 = 5000;
-
-    pub fn new() -> Self {
+    pub fn new(journal_path: PathBuf) -> Self {
         let (proxy_sender, proxy_receiver) = mpsc::channel();
-        let proxy_sender_clone = proxy_sender.clone();
-        let proxy_thread =
-            thread::spawn(move || Self::run_proxy(proxy_sender_clone, proxy_receiver));
+        let proxy_thread = thread::spawn(move || Self::run_proxy(proxy_receiver, journal_path));
 
         MuteProxy {
             proxy_sender,
@@ -53,130 +515,228 @@ impl MuteProxy {
         self.proxy_thread.join()
     }
 
-    pub fn mute(&self, pid: DWORD) {
-        self.proxy_sender
-            .send(MuteProxyMessage::Mute(pid))
-            .expect("failed to send message to mute proxy");
+    pub fn mute(&self, process: ProcessId) {
+        self.send(MuteProxyMessage::Mute(process));
+    }
+
+    /// Lowers the volume of a process to `volume`, until it is unmuted again.
+    pub fn duck(&self, process: ProcessId, volume: f64) {
+        self.send(MuteProxyMessage::Duck(process, volume as f32));
     }
 
-    pub fn unmute(&self, pid: DWORD, aggressive: bool) {
+    /// Moves a process to the output device whose name contains `device_name`, until it is
+    /// unmuted again. Mutes it instead if there is no such device.
+    pub fn reroute(&self, process: ProcessId, device_name: &str) {
+        self.send(MuteProxyMessage::Reroute(process, device_name.into()));
+    }
+
+    /// Mutes the microphone recording of a process, until it is unmuted again.
+    pub fn mute_capture(&self, process: ProcessId) {
+        self.send(MuteProxyMessage::MuteCapture(process));
+    }
+
+    /// Undoes the changes made to a process. Processes the user muted by hand stay muted.
+    pub fn unmute(&self, process: ProcessId) {
+        self.send(MuteProxyMessage::Unmute(process));
+    }
+
+    /// Undoes a mute state that a new audio session got from a process annie left muted.
+    pub fn session_created(&self, process: ProcessId) {
+        self.send(MuteProxyMessage::SessionCreated(process));
+    }
+
+    /// Unmutes a process, even if it was muted by hand.
+    pub fn force_unmute(&self, process: ProcessId) {
+        self.send(MuteProxyMessage::ForceUnmute(process));
+    }
+
+    /// Undoes the changes made to all processes.
+    pub fn restore_all(&self) {
+        self.send(MuteProxyMessage::RestoreAll);
+    }
+
+    /// Clears the output device setting an exited process of the same program left behind.
+    pub fn process_started(&self, process: ProcessId) {
+        self.send(MuteProxyMessage::ProcessStarted(process));
+    }
+
+    /// Forgets the changes made to a process that has exited, so they are not applied to a later
+    /// process with the same PID.
+    pub fn process_exited(&self, process: ProcessId) {
+        self.send(MuteProxyMessage::ProcessExited(process));
+    }
+
+    fn send(&self, message: MuteProxyMessage) {
         self.proxy_sender
-            .send(MuteProxyMessage::Unmute(pid, aggressive))
+            .send(message)
             .expect("failed to send message to mute proxy");
     }
 
-    fn run_proxy(sender: Sender<MuteProxyMessage>, receiver: Receiver<MuteProxyMessage>) {
+    fn run_proxy(receiver: Receiver<MuteProxyMessage>, journal_path: PathBuf) {
         info!("Mute proxy start");
 
-        let mut currently_unmuting = HashSet::<DWORD>::new();
+        // undo what a previous run left behind, before applying anything new
+        let mut journal = MuteJournal::new(journal_path);
+        let mut changes = AppliedChanges::from_journal_file(journal.load_leftover());
+        let leftover_count = changes.get_changed_processes().len();
+
+        if leftover_count > 0 {
+            info!(
+                "Restoring {} processes left changed by an unclean exit",
+                leftover_count
+            );
+            changes.restore_all();
+        }
+
+        changes.reset_endpoints();
+        journal.write(changes.get_journal_file());
 
         while let Ok(message) = receiver.recv() {
             debug!("Mute proxy received message: {:?}", &message);
 
+            // the process may have exited while the message was queued
+            if let Some(process) = message
+                .get_process()
+                .filter(|process| !process.is_running())
+            {
+                debug!("Process {} has exited, ignoring message", process.pid);
+                changes.forget(process);
+                journal.write(changes.get_journal_file());
+                continue;
+            }
+
             match message {
-                MuteProxyMessage::Mute(pid) => {
-                    Self::set_mute_synchronous(pid, true);
-                    currently_unmuting.remove(&pid);
-                }
-                MuteProxyMessage::Unmute(pid, aggressive) => {
-                    Self::set_mute_synchronous(pid, false);
-
-                    if aggressive && !currently_unmuting.contains(&pid) {
-                        if let Some(start_time) = Self::get_process_start_time(pid) {
-                            if Self::get_ms_since(start_time) < Self::PROCESS_AGE_THRESHOLD_MS {
-                                info!("Process {} is newly opened, unmuting several times", pid);
-                                currently_unmuting.insert(pid);
-                                Self::unmute_followup_delayed(&sender, pid, start_time);
-                            }
+                MuteProxyMessage::Mute(process) => changes.mute(process),
+                MuteProxyMessage::Duck(process, volume) => changes.duck(process, volume),
+                MuteProxyMessage::Reroute(process, device_name) => {
+                    match find_output_device(&device_name) {
+                        Some(device) => changes.reroute(process, &device),
+                        None => {
+                            warn!(
+                                "No output device matches \"{}\", muting process {} instead",
+                                device_name, process.pid
+                            );
+                            changes.mute(process);
                         }
                     }
                 }
-                MuteProxyMessage::UnmuteFollowup(pid, start_time) => {
-                    if currently_unmuting.contains(&pid) {
-                        let process_age_ms = Self::get_ms_since(start_time);
-
-                        if process_age_ms < Self::PROCESS_AGE_THRESHOLD_MS {
-                            // process is not old enough -> mute and continue
-                            Self::set_mute_synchronous(pid, false);
-                            Self::unmute_followup_delayed(&sender, pid, start_time);
-                        } else if process_age_ms < Self::PROCESS_AGE_THRESHOLD_MS + 1000 {
-                            // process age is close beyond threshold -> mute and end
-                            Self::set_mute_synchronous(pid, false);
-                            currently_unmuting.remove(&pid);
-                        } else {
-                            // process is old enough -> end
-                            currently_unmuting.remove(&pid);
-                        }
-                    }
+                MuteProxyMessage::MuteCapture(process) => changes.mute_capture(process),
+                MuteProxyMessage::Unmute(process) => changes.restore(process),
+                MuteProxyMessage::SessionCreated(process) => {
+                    changes.reset_mute_of_new_session(process)
                 }
+                MuteProxyMessage::ForceUnmute(process) => {
+                    changes.restore(process);
+                    Self::set_mute_synchronous(process.pid, false);
+                }
+                MuteProxyMessage::RestoreAll => changes.restore_all(),
+                MuteProxyMessage::ProcessStarted(process) => {
+                    changes.reset_endpoint_of_started(process)
+                }
+                MuteProxyMessage::ProcessExited(process) => changes.forget(process),
             }
+
+            journal.write(changes.get_journal_file());
         }
 
-        info!("Mute proxy exit"); // TODO: This is not reached - why?
+        info!("Mute proxy exit");
     }
 
-    fn unmute_followup_delayed(
-        sender: &Sender<MuteProxyMessage>,
-        pid: DWORD,
-        start_time: SystemTime,
-    ) {
-        let sender = sender.clone();
-        thread::spawn(move || {
-            thread::sleep(Duration::from_millis(1000));
-            sender
-                .send(MuteProxyMessage::UnmuteFollowup(pid, start_time))
-                .expect("failed to send message to mute proxy");
-        });
+    /// Mutes or unmutes the sessions of a process on every output device, and logs which
+    /// devices were touched.
+    fn set_mute_synchronous(pid: DWORD, mute: bool) {
+        let action = if mute { "Muted" } else { "Unmuted" };
+        let mut devices_buf = vec![0 as WCHAR; MAX_DEVICES * DEVICE_STRING_LEN];
+
+        let device_count = unsafe {
+            SetProcessMute(
+                pid,
+                mute as _,
+                devices_buf.as_mut_ptr(),
+                devices_buf.len() as _,
+            )
+        };
+
+        if device_count == 0 {
+            debug!(
+                "{} process {} on no device, it has no audio session",
+                action, pid
+            );
+            return;
+        }
+
+        let devices = from_wide(&devices_buf).lines().join(", ");
+
+        info!("{} process {} on {}", action, pid, devices);
     }
 
-    fn get_process_start_time(pid: DWORD) -> Option<SystemTime> {
+    fn set_capture_mute_synchronous(pid: DWORD, mute: bool) {
+        if mute {
+            info!("Muting recording of process {}", pid);
+        } else {
+            info!("Unmuting recording of process {}", pid);
+        }
+
         unsafe {
-            let hproc = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
-            if hproc == 0 as _ {
-                warn!(
-                    "Failed to get process handle for process {}: {}",
-                    pid,
-                    Win32Error::get_last_error()
-                );
+            // ignore hresult - can't do anything useful with the error anyway
+            SetProcessCaptureMute(pid, mute as _);
+        }
+    }
 
+    fn get_mute_synchronous(pid: DWORD, capture: bool) -> Option<bool> {
+        let mut muted = FALSE;
+
+        unsafe {
+            if GetProcessMute(pid, capture as _, &mut muted) == FALSE {
                 return None;
             }
+        }
 
-            let mut filetime: FILETIME = mem::zeroed();
-            GetProcessTimes(
-                hproc,
-                &mut filetime,
-                MaybeUninit::uninit().as_mut_ptr(),
-                MaybeUninit::uninit().as_mut_ptr(),
-                MaybeUninit::uninit().as_mut_ptr(),
-            );
-
-            CloseHandle(hproc);
+        Some(muted != FALSE)
+    }
 
-            let time_started =
-                ((filetime.dwHighDateTime as u64) << 32) + (filetime.dwLowDateTime as u64);
+    fn get_volume_synchronous(pid: DWORD) -> Option<f32> {
+        let mut volume = 0.0;
 
-            Some(mem::transmute(time_started))
+        unsafe {
+            if GetProcessVolume(pid, &mut volume) == FALSE {
+                return None;
+            }
         }
-    }
 
-    fn get_ms_since(timestamp: SystemTime) -> u128 {
-        SystemTime::now()
-            .duration_since(timestamp)
-            .expect("start time after end time")
-            .as_millis()
+        Some(volume)
     }
 
-    fn set_mute_synchronous(pid: DWORD, mute: bool) {
-        if mute {
-            info!("Muting process {}", pid);
-        } else {
-            info!("Unmuting process {}", pid);
+    fn set_volume_synchronous(pid: DWORD, volume: f32) {
+        info!("Setting volume of process {} to {}", pid, volume);
+
+        unsafe {
+            // ignore hresult - can't do anything useful with the error anyway
+            SetProcessVolume(pid, volume);
         }
+    }
+
+    fn set_output_device_synchronous(pid: DWORD, device: Option<&OutputDevice>) {
+        let device_id = match device {
+            Some(device) => {
+                info!("Moving process {} to output device {}", pid, device.name);
+                Some(device.id.encode_utf16().chain(iter::once(0)).collect_vec())
+            }
+            None => {
+                info!("Moving process {} back to the default output device", pid);
+                None
+            }
+        };
 
         unsafe {
             // ignore hresult - can't do anything useful with the error anyway
-            SetProcessMute(pid, mute as _);
+            SetProcessOutputDevice(
+                pid,
+                device_id
+                    .as_ref()
+                    .map(|id| id.as_ptr())
+                    .unwrap_or(ptr::null()),
+            );
         }
     }
 }
@@ -0,0 +1,185 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use winapi::shared::minwindef::DWORD;
+
+/// The state of a process from before annie changed it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub pid: DWORD,
+    pub start_time: u64,
+    pub program_path: String,
+    pub muted_before: Option<bool>,
+    pub volume_before: Option<f32>,
+    pub rerouted: bool,
+    pub capture_muted_before: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct JournalFile {
+    #[serde(default)]
+    pub entries: Vec<JournalEntry>,
+    /// Programs that were rerouted when their process exited. Their output device is reset the
+    /// next time they run.
+    #[serde(default)]
+    pub endpoint_resets: Vec<String>,
+    /// Programs whose next audio session is to be unmuted, since annie left their last one muted.
+    #[serde(default)]
+    pub mute_resets: Vec<String>,
+}
+
+impl JournalFile {
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.endpoint_resets.is_empty() && self.mute_resets.is_empty()
+    }
+}
+
+/// Keeps a file with every change annie currently applies to processes, so that they can be
+/// undone on the next start if annie does not exit cleanly. The file is removed once nothing is
+/// left to undo.
+pub struct MuteJournal {
+    path: PathBuf,
+    written_file: JournalFile,
+}
+
+impl MuteJournal {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        MuteJournal {
+            path: path.as_ref().into(),
+            written_file: JournalFile::default(),
+        }
+    }
+
+    /// Reads the journal left behind by the previous run.
+    pub fn load_leftover(&mut self) -> JournalFile {
+        let payload = match fs::read_to_string(&self.path) {
+            Ok(payload) => payload,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return JournalFile::default(),
+            Err(err) => {
+                error!("Cannot read mute journal at {:?}: {}", self.path, err);
+                return JournalFile::default();
+            }
+        };
+
+        match toml::from_str::<JournalFile>(&payload) {
+            Ok(journal_file) => {
+                self.written_file = journal_file.clone();
+                journal_file
+            }
+            Err(err) => {
+                warn!(
+                    "Ignoring malformed mute journal at {:?}: {}",
+                    self.path, err
+                );
+                fs::remove_file(&self.path).ok();
+                JournalFile::default()
+            }
+        }
+    }
+
+    /// Replaces the journal with `journal_file`. Does nothing if it was already written.
+    pub fn write(&mut self, journal_file: JournalFile) {
+        if journal_file == self.written_file {
+            return;
+        }
+
+        let result = if journal_file.is_empty() {
+            match fs::remove_file(&self.path) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+                result => result.map_err(anyhow::Error::from),
+            }
+        } else {
+            toml::to_string(&journal_file)
+                .map_err(anyhow::Error::from)
+                .and_then(|payload| self.replace_file(payload).map_err(anyhow::Error::from))
+        };
+
+        match result {
+            Ok(()) => self.written_file = journal_file,
+            Err(err) => error!("Cannot write mute journal to {:?}: {}", self.path, err),
+        }
+    }
+
+    /// Writes a temporary file next to the journal and moves it over the journal, so that a crash
+    /// while writing never leaves a truncated journal behind.
+    fn replace_file(&self, payload: String) -> io::Result<()> {
+        let temp_path = self.path.with_extension("toml.tmp");
+        fs::write(&temp_path, payload)?;
+        fs::rename(&temp_path, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn temp_journal_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("annie-test-{}-{}.toml", name, process::id()))
+    }
+
+    fn journal_file() -> JournalFile {
+        JournalFile {
+            entries: vec![
+                JournalEntry {
+                    pid: 1234,
+                    start_time: 133_000_000_000_000_000,
+                    program_path: "C:\\Program Files\\Spotify\\Spotify.exe".into(),
+                    muted_before: Some(false),
+                    volume_before: Some(0.5),
+                    rerouted: true,
+                    capture_muted_before: None,
+                },
+                JournalEntry {
+                    pid: 5678,
+                    start_time: 133_000_000_000_000_001,
+                    program_path: "C:\\Users\\me\\Discord\\Discord.exe".into(),
+                    muted_before: None,
+                    volume_before: None,
+                    rerouted: false,
+                    capture_muted_before: Some(false),
+                },
+            ],
+            endpoint_resets: vec!["C:\\Program Files\\foobar2000\\foobar2000.exe".into()],
+            mute_resets: vec!["C:\\Program Files\\VideoLAN\\VLC\\vlc.exe".into()],
+        }
+    }
+
+    #[test]
+    fn write_and_load_round_trip() {
+        let path = temp_journal_path("journal-round-trip");
+        MuteJournal::new(&path).write(journal_file());
+
+        let loaded = MuteJournal::new(&path).load_leftover();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(loaded, journal_file());
+    }
+
+    #[test]
+    fn empty_write_removes_file() {
+        let path = temp_journal_path("journal-empty");
+        let mut journal = MuteJournal::new(&path);
+        journal.write(journal_file());
+        assert!(path.exists());
+
+        journal.write(JournalFile::default());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn malformed_file_is_ignored() {
+        let path = temp_journal_path("journal-malformed");
+        fs::write(&path, "entries = 42").unwrap();
+
+        let loaded = MuteJournal::new(&path).load_leftover();
+
+        assert_eq!(loaded, JournalFile::default());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,131 @@
+use std::{collections::HashSet, mem};
+
+use log::error;
+use unicase::UniCase;
+use winapi::{
+    shared::minwindef::{DWORD, FILETIME},
+    um::{
+        handleapi::{CloseHandle, INVALID_HANDLE_VALUE},
+        processthreadsapi::{GetProcessTimes, OpenProcess},
+        tlhelp32::{
+            CreateToolhelp32Snapshot, Process32First, Process32Next, PROCESSENTRY32,
+            TH32CS_SNAPPROCESS,
+        },
+        winbase::QueryFullProcessImageNameW,
+        winnt::{PROCESS_QUERY_INFORMATION, PROCESS_QUERY_LIMITED_INFORMATION},
+    },
+};
+
+use crate::core::ProgramPath;
+
+/// Identifies a process. The start time tells it apart from a later process that reuses the PID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProcessId {
+    pub pid: DWORD,
+    pub start_time: u64,
+}
+
+impl ProcessId {
+    /// The process that currently has `pid`, if any.
+    pub fn from_pid(pid: DWORD) -> Option<Self> {
+        let start_time = get_process_start_time(pid)?;
+        Some(ProcessId { pid, start_time })
+    }
+
+    pub fn is_running(&self) -> bool {
+        get_process_start_time(self.pid) == Some(self.start_time)
+    }
+
+    /// The program path of the process, unless it has exited in the meantime.
+    pub fn get_program_path(&self) -> Option<ProgramPath> {
+        let program_path = get_path_from_pid(self.pid)?;
+        self.is_running().then_some(program_path)
+    }
+}
+
+/// All running processes whose start time can be queried. Others (e.g. protected system
+/// processes) can't be muted anyway.
+pub fn get_running_processes() -> Option<HashSet<ProcessId>> {
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if snapshot == INVALID_HANDLE_VALUE {
+            error!("Failed to retrieve process snapshot");
+            return None;
+        }
+
+        let mut processes = HashSet::new();
+        let mut process_entry: PROCESSENTRY32 = mem::zeroed();
+        process_entry.dwSize = mem::size_of::<PROCESSENTRY32>() as _;
+        let mut hresult = Process32First(snapshot, &mut process_entry);
+
+        while hresult > 0 {
+            if let Some(process) = ProcessId::from_pid(process_entry.th32ProcessID) {
+                processes.insert(process);
+            }
+            hresult = Process32Next(snapshot, &mut process_entry);
+        }
+
+        CloseHandle(snapshot);
+
+        Some(processes)
+    }
+}
+
+pub fn get_path_from_pid(pid: DWORD) -> Option<ProgramPath> {
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_INFORMATION, 0, pid);
+        if handle == 0 as _ {
+            return None;
+        }
+
+        let mut process_path_buf = [0u16; 1024];
+        let mut buf_size: DWORD = process_path_buf.len() as _;
+        let ok =
+            QueryFullProcessImageNameW(handle, 0, process_path_buf.as_mut_ptr(), &mut buf_size);
+        CloseHandle(handle);
+
+        if ok == 0 {
+            return None;
+        }
+
+        let program_path = String::from_utf16(&process_path_buf[..(buf_size as _)]).ok()?;
+        Some(UniCase::new(program_path.into()))
+    }
+}
+
+/// The creation time of a process as a `FILETIME` value.
+pub fn get_process_start_time(pid: DWORD) -> Option<u64> {
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle == 0 as _ {
+            return None;
+        }
+
+        let mut creation_time: FILETIME = mem::zeroed();
+        let mut exit_time: FILETIME = mem::zeroed();
+        let mut kernel_time: FILETIME = mem::zeroed();
+        let mut user_time: FILETIME = mem::zeroed();
+        let ok = GetProcessTimes(
+            handle,
+            &mut creation_time,
+            &mut exit_time,
+            &mut kernel_time,
+            &mut user_time,
+        );
+        CloseHandle(handle);
+
+        if ok == 0 {
+            return None;
+        }
+
+        Some((creation_time.dwHighDateTime as u64) << 32 | creation_time.dwLowDateTime as u64)
+    }
+}
+
+pub fn get_processes_from_path(program_path: &ProgramPath) -> Vec<ProcessId> {
+    get_running_processes()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|process| get_path_from_pid(process.pid).as_ref() == Some(program_path))
+        .collect()
+}
@@ -0,0 +1,75 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use log::info;
+
+use crate::{
+    core::{CoreMessage, CoreSender},
+    process,
+};
+
+const POLL_INTERVAL_MS: u64 = 1000;
+
+/// Polls the process list and reports to the core whenever a process starts or exits.
+/// Processes that are already running when the monitor starts are reported as started.
+pub struct ProcessMonitorHandle {
+    stop_flag: Arc<AtomicBool>,
+    monitor_thread: JoinHandle<()>,
+}
+
+impl ProcessMonitorHandle {
+    pub fn spawn(core_sender: CoreSender) -> Self {
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let stop_flag_clone = stop_flag.clone();
+        let monitor_thread = thread::spawn(move || run_monitor(core_sender, stop_flag_clone));
+
+        ProcessMonitorHandle {
+            stop_flag,
+            monitor_thread,
+        }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.stop_flag.store(true, Ordering::SeqCst);
+        self.monitor_thread.join()
+    }
+}
+
+fn run_monitor(core_sender: CoreSender, stop_flag: Arc<AtomicBool>) {
+    info!("Process monitor start");
+
+    let mut running_processes = HashSet::new();
+
+    while !stop_flag.load(Ordering::SeqCst) {
+        // keep the old state if the snapshot fails, instead of reporting every process as exited
+        if let Some(running_processes_new) = process::get_running_processes() {
+            // report exits first, so that a restarted program is seen in order
+            let exited = running_processes
+                .difference(&running_processes_new)
+                .map(|&process| CoreMessage::ProcessExited(process));
+            let started = running_processes_new
+                .difference(&running_processes)
+                .map(|&process| CoreMessage::ProcessStarted(process));
+
+            for message in exited.chain(started) {
+                if core_sender.send(message).is_err() {
+                    info!("Process monitor exit");
+                    return;
+                }
+            }
+
+            running_processes = running_processes_new;
+        }
+
+        thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+    }
+
+    info!("Process monitor exit");
+}
@@ -0,0 +1,215 @@
+use std::{
+    iter,
+    os::windows::prelude::AsRawHandle,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use log::info;
+use once_cell::sync::Lazy;
+use winapi::{
+    shared::{
+        minwindef::{BOOL, DWORD, LPARAM, LRESULT, UINT, WPARAM},
+        windef::HWND,
+    },
+    um::{
+        libloaderapi::GetModuleHandleW,
+        processthreadsapi::GetThreadId,
+        sysinfoapi::GetTickCount,
+        winuser::{
+            CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetLastInputInfo,
+            GetMessageW, KillTimer, PostQuitMessage, PostThreadMessageW, RegisterClassW, SetTimer,
+            TranslateMessage, HWND_MESSAGE, LASTINPUTINFO, WM_TIMER, WM_USER, WNDCLASSW,
+        },
+    },
+};
+
+use crate::core::{CoreMessage, CoreSender};
+
+// not exposed by winapi
+#[link(name = "wtsapi32")]
+extern "system" {
+    fn WTSRegisterSessionNotification(hWnd: HWND, dwFlags: DWORD) -> BOOL;
+    fn WTSUnRegisterSessionNotification(hWnd: HWND) -> BOOL;
+}
+
+const NOTIFY_FOR_THIS_SESSION: DWORD = 0;
+const WM_WTSSESSION_CHANGE: UINT = 0x02B1;
+const WTS_SESSION_LOCK: WPARAM = 0x7;
+const WTS_SESSION_UNLOCK: WPARAM = 0x8;
+
+const WM_STOP_LISTENING: UINT = WM_USER + 1;
+const IDLE_TIMER_ID: usize = 1;
+const IDLE_POLL_INTERVAL_MS: UINT = 1000;
+
+// window procedures don't accept custom data -> communicate via statics
+static CORE_SENDER: Lazy<Mutex<Option<CoreSender>>> = Lazy::new(|| Mutex::new(None));
+static IDLE_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(0);
+static IS_IDLE: AtomicBool = AtomicBool::new(false);
+
+fn send_to_core(message: CoreMessage) {
+    let Some(lock) = CORE_SENDER.lock().ok() else { return };
+    if let Some(core_sender) = &*lock {
+        core_sender.send(message).ok();
+    }
+}
+
+unsafe fn check_idle() {
+    let idle_timeout_secs = IDLE_TIMEOUT_SECS.load(Ordering::SeqCst);
+
+    let is_idle = if idle_timeout_secs > 0 {
+        let mut last_input_info = LASTINPUTINFO {
+            cbSize: std::mem::size_of::<LASTINPUTINFO>() as _,
+            dwTime: 0,
+        };
+        GetLastInputInfo(&mut last_input_info);
+
+        let idle_ms = GetTickCount().wrapping_sub(last_input_info.dwTime) as u64;
+        idle_ms >= idle_timeout_secs * 1000
+    } else {
+        false
+    };
+
+    if IS_IDLE.swap(is_idle, Ordering::SeqCst) != is_idle {
+        send_to_core(CoreMessage::SessionIdle(is_idle));
+    }
+}
+
+unsafe fn session_listener_loop() {
+    unsafe extern "system" fn session_window_proc(
+        hwnd: HWND,
+        msg: UINT,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        match (msg, wparam) {
+            (WM_WTSSESSION_CHANGE, WTS_SESSION_LOCK) => {
+                send_to_core(CoreMessage::SessionLocked(true));
+                0
+            }
+            (WM_WTSSESSION_CHANGE, WTS_SESSION_UNLOCK) => {
+                send_to_core(CoreMessage::SessionLocked(false));
+                0
+            }
+            (WM_TIMER, IDLE_TIMER_ID) => {
+                check_idle();
+                0
+            }
+            _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+        }
+    }
+
+    info!("Session listener start");
+
+    // create a message-only window to receive session notifications
+
+    let class_name = "annie-session-listener"
+        .encode_utf16()
+        .chain(iter::once(0))
+        .collect::<Vec<_>>();
+    let hinstance = GetModuleHandleW(0 as _);
+
+    let mut window_class: WNDCLASSW = std::mem::zeroed();
+    window_class.lpfnWndProc = Some(session_window_proc);
+    window_class.hInstance = hinstance;
+    window_class.lpszClassName = class_name.as_ptr();
+
+    if RegisterClassW(&window_class) == 0 {
+        panic!("could not register session listener window class");
+    }
+
+    let hwnd = CreateWindowExW(
+        0,
+        class_name.as_ptr(),
+        class_name.as_ptr(),
+        0,
+        0,
+        0,
+        0,
+        0,
+        HWND_MESSAGE,
+        0 as _,
+        hinstance,
+        0 as _,
+    );
+
+    if hwnd == (0 as _) {
+        panic!("could not create session listener window");
+    }
+
+    if WTSRegisterSessionNotification(hwnd, NOTIFY_FOR_THIS_SESSION) == 0 {
+        panic!("could not register for session notifications");
+    }
+
+    SetTimer(hwnd, IDLE_TIMER_ID, IDLE_POLL_INTERVAL_MS, None);
+
+    // run message loop
+
+    let mut msg = std::mem::zeroed();
+
+    while GetMessageW(&mut msg, 0 as _, 0, 0) != 0 {
+        match msg.message {
+            WM_STOP_LISTENING => {
+                PostQuitMessage(0);
+            }
+            _ => {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+
+    // cleanup
+
+    KillTimer(hwnd, IDLE_TIMER_ID);
+    WTSUnRegisterSessionNotification(hwnd);
+    DestroyWindow(hwnd);
+
+    CORE_SENDER
+        .lock()
+        .expect("cannot lock core sender mutex")
+        .take()
+        .expect("sender is empty during session listener exit");
+
+    info!("Session listener exit");
+}
+
+pub struct SessionListenerHandle {
+    listener_thread: JoinHandle<()>,
+}
+
+impl SessionListenerHandle {
+    pub fn spawn(core_sender: CoreSender) -> Self {
+        let old_sender = CORE_SENDER
+            .lock()
+            .expect("cannot lock core sender mutex")
+            .replace(core_sender);
+
+        assert!(old_sender.is_none(), "dangling core sender");
+
+        SessionListenerHandle {
+            listener_thread: thread::spawn(|| unsafe { session_listener_loop() }),
+        }
+    }
+
+    /// Sets after how many seconds without input the session counts as idle. 0 disables this.
+    pub fn set_idle_timeout(&self, idle_timeout_secs: u64) {
+        IDLE_TIMEOUT_SECS.store(idle_timeout_secs, Ordering::SeqCst);
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        unsafe {
+            let thread_id = GetThreadId(self.listener_thread.as_raw_handle() as _);
+            let success = PostThreadMessageW(thread_id, WM_STOP_LISTENING, 0, 0);
+            assert!(
+                success > 0,
+                "failed to post WM_STOP_LISTENING to session listener thread"
+            );
+        }
+
+        self.listener_thread.join()
+    }
+}
@@ -1,10 +1,11 @@
 use std::{
     borrow::Cow,
-    collections::{HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     iter,
     path::Path,
     sync::mpsc::{self},
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use itertools::Itertools;
@@ -12,35 +13,49 @@ use log::{debug, error, info};
 
 use msgbox::IconType;
 use winit::{
-    event::Event,
+    event::{Event, StartCause},
     event_loop::{ControlFlow, EventLoop, EventLoopProxy},
     platform::windows::EventLoopExtWindows,
 };
 
 use trayicon::{Icon, MenuBuilder, TrayIcon, TrayIconBuilder};
 
-use crate::core::{CoreMessage, CoreSender, ProgramPath};
+use crate::{
+    config::AppCategory,
+    core::{CoreMessage, CoreSender, Exemption, ProgramPath},
+};
 
 pub type TraySender = EventLoopProxy<TrayEvent>;
 
 const TRAY_ICON_BLUE: &[u8] = include_bytes!("../resource/annie-small-blue.ico");
 const TRAY_ICON_RED: &[u8] = include_bytes!("../resource/annie-small-red.ico");
 
+const EXEMPTION_MINUTES: &[u64] = &[15, 30, 60, 120];
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum TrayEvent {
     // UI events
     ToggleGlobal,
     ToggleProgram(usize),
+    TogglePinnedProgram(usize),
+    SetProgramCategory(usize, AppCategory),
+    SetProgramExemption(usize, Option<Exemption>),
     OpenConfig,
     ReloadConfig,
     ForceUnmuteAll,
+    ShowStatus,
+    ShowAudioSessions,
     ShowAbout,
     Exit,
     // core events
     AddRecentApp(ProgramPath, bool),
+    UpdatePinnedApp(Option<ProgramPath>),
+    UpdateAppCategory(ProgramPath, AppCategory),
+    UpdateExemptApps(HashMap<ProgramPath, Option<Instant>>),
     UpdateFromConfig {
         enabled: bool,
         managed_apps: HashSet<ProgramPath>,
+        app_categories: HashMap<ProgramPath, AppCategory>,
         max_recent_apps: usize,
     },
 }
@@ -50,6 +65,9 @@ struct TrayState {
     enabled: bool,
     recent_apps: VecDeque<(ProgramPath, bool)>,
     max_recent_apps: usize,
+    pinned_app: Option<ProgramPath>,
+    app_categories: HashMap<ProgramPath, AppCategory>,
+    exempt_apps: HashMap<ProgramPath, Option<Instant>>,
 }
 
 fn update_tray_app(tray_app: &mut TrayIcon<TrayEvent>, tray_state: &TrayState) {
@@ -58,10 +76,84 @@ fn update_tray_app(tray_app: &mut TrayIcon<TrayEvent>, tray_state: &TrayState) {
     let mut recent_apps_menu = MenuBuilder::new();
 
     for (index, (app_path, app_active)) in tray_state.recent_apps.iter().enumerate() {
-        recent_apps_menu = recent_apps_menu.checkable(
+        let app_text = match tray_state.exempt_apps.get(app_path) {
+            Some(expiry) => format!(
+                "{} - {}",
+                get_app_tray_text(app_path),
+                get_exemption_text(expiry)
+            ),
+            None => get_app_tray_text(app_path),
+        };
+
+        recent_apps_menu =
+            recent_apps_menu.checkable(&app_text, *app_active, TrayEvent::ToggleProgram(index));
+    }
+
+    // background exemption submenu (part of recent apps submenu)
+
+    let mut exemption_menu = MenuBuilder::new();
+
+    for (index, (app_path, _)) in tray_state.recent_apps.iter().enumerate() {
+        let mut app_exemption_menu = MenuBuilder::new().item(
+            "For this session",
+            TrayEvent::SetProgramExemption(index, Some(Exemption::Session)),
+        );
+
+        for &minutes in EXEMPTION_MINUTES {
+            app_exemption_menu = app_exemption_menu.item(
+                &format!("For {} minutes", minutes),
+                TrayEvent::SetProgramExemption(index, Some(Exemption::Minutes(minutes))),
+            );
+        }
+
+        if tray_state.exempt_apps.contains_key(app_path) {
+            app_exemption_menu = app_exemption_menu.separator().item(
+                "Stop playing in background",
+                TrayEvent::SetProgramExemption(index, None),
+            );
+        }
+
+        exemption_menu = exemption_menu.submenu(&get_app_tray_text(app_path), app_exemption_menu);
+    }
+
+    // app category submenu (part of recent apps submenu)
+
+    let mut category_menu = MenuBuilder::new();
+
+    for (index, (app_path, _)) in tray_state.recent_apps.iter().enumerate() {
+        let app_category = tray_state
+            .app_categories
+            .get(app_path)
+            .copied()
+            .unwrap_or(AppCategory::Other);
+
+        let mut app_category_menu = MenuBuilder::new();
+
+        for category in AppCategory::ALL {
+            app_category_menu = app_category_menu.checkable(
+                &category.to_string(),
+                category == app_category,
+                TrayEvent::SetProgramCategory(index, category),
+            );
+        }
+
+        category_menu = category_menu.submenu(&get_app_tray_text(app_path), app_category_menu);
+    }
+
+    recent_apps_menu = recent_apps_menu
+        .separator()
+        .submenu("Keep playing in background", exemption_menu)
+        .submenu("App category", category_menu);
+
+    // pin audio submenu
+
+    let mut pin_menu = MenuBuilder::new();
+
+    for (index, (app_path, _)) in tray_state.recent_apps.iter().enumerate() {
+        pin_menu = pin_menu.checkable(
             &get_app_tray_text(app_path),
-            *app_active,
-            TrayEvent::ToggleProgram(index),
+            tray_state.pinned_app.as_ref() == Some(app_path),
+            TrayEvent::TogglePinnedProgram(index),
         );
     }
 
@@ -70,11 +162,14 @@ fn update_tray_app(tray_app: &mut TrayIcon<TrayEvent>, tray_state: &TrayState) {
     let menu = MenuBuilder::new()
         .checkable("Enable muting", tray_state.enabled, TrayEvent::ToggleGlobal)
         .submenu("Recent apps", recent_apps_menu)
+        .submenu("Pin audio to this app", pin_menu)
         .separator()
         .item("Show config file", TrayEvent::OpenConfig)
         .item("Reload config from file", TrayEvent::ReloadConfig)
         .item("Force unmute all apps", TrayEvent::ForceUnmuteAll)
         .separator()
+        .item("Show status", TrayEvent::ShowStatus)
+        .item("Show audio sessions", TrayEvent::ShowAudioSessions)
         .item("About", TrayEvent::ShowAbout)
         .item("Exit", TrayEvent::Exit);
     tray_app.set_menu(&menu).expect("failed to set tray menu");
@@ -91,11 +186,17 @@ fn update_tray_app(tray_app: &mut TrayIcon<TrayEvent>, tray_state: &TrayState) {
         .expect("cannot update tray icon");
 
     // tooltip
+
+    let tooltip = match (tray_state.enabled, &tray_state.pinned_app) {
+        (false, _) => Cow::Borrowed("Annie (disabled)"),
+        (true, Some(pinned_app)) => {
+            Cow::Owned(format!("Annie (pinned to {})", get_app_name(pinned_app)))
+        }
+        (true, None) => Cow::Borrowed("Annie"),
+    };
+
     tray_app
-        .set_tooltip(match tray_state.enabled {
-            true => "Annie",
-            false => "Annie (disabled)",
-        })
+        .set_tooltip(&tooltip)
         .expect("cannot update tray tooltip");
 }
 
@@ -127,6 +228,38 @@ fn get_app_tray_text(app_path: &str) -> String {
     format!("{} ({})", get_app_name(app_path), app_path)
 }
 
+fn get_minutes_left(expiry: Instant, now: Instant) -> u64 {
+    let millis_left = expiry.saturating_duration_since(now).as_millis() as u64;
+    (millis_left + 59_999) / 60_000
+}
+
+fn get_exemption_text(expiry: &Option<Instant>) -> String {
+    match expiry {
+        Some(expiry) => {
+            let minutes_left = get_minutes_left(*expiry, Instant::now()).max(1);
+            format!("playing in background, {} min left", minutes_left)
+        }
+        None => "playing in background".into(),
+    }
+}
+
+/// When the minutes left of the next timed exemption change, so that the menu can be refreshed.
+fn get_exemption_refresh_time(
+    exempt_apps: &HashMap<ProgramPath, Option<Instant>>,
+) -> Option<Instant> {
+    let now = Instant::now();
+
+    exempt_apps
+        .values()
+        .flatten()
+        .filter(|expiry| **expiry > now)
+        .map(|expiry| {
+            let minutes_left = get_minutes_left(*expiry, now);
+            *expiry - Duration::from_secs(minutes_left.saturating_sub(1) * 60)
+        })
+        .min()
+}
+
 fn show_about_message() {
     thread::spawn(|| {
         let body = format!(
@@ -173,7 +306,14 @@ pub fn create_tray_thread(core_sender: CoreSender) -> (JoinHandle<()>, TraySende
         update_tray_app(&mut tray_app, &tray_state);
 
         event_loop.run(move |event, _, control_flow| {
-            *control_flow = ControlFlow::Wait;
+            *control_flow = match get_exemption_refresh_time(&tray_state.exempt_apps) {
+                Some(refresh_time) => ControlFlow::WaitUntil(refresh_time),
+                None => ControlFlow::Wait,
+            };
+
+            if let Event::NewEvents(StartCause::ResumeTimeReached { .. }) = event {
+                update_tray_app(&mut tray_app, &tray_state);
+            }
 
             if let Event::UserEvent(user_event) = event {
                 debug!("Tray received event: {:?}", &user_event);
@@ -192,10 +332,13 @@ pub fn create_tray_thread(core_sender: CoreSender) -> (JoinHandle<()>, TraySende
                     TrayEvent::UpdateFromConfig {
                         enabled,
                         managed_apps,
+                        app_categories,
                         max_recent_apps,
                     } => {
                         tray_state.enabled = enabled;
 
+                        tray_state.app_categories = app_categories;
+
                         tray_state.max_recent_apps = max_recent_apps;
 
                         if tray_state.recent_apps.len() > max_recent_apps {
@@ -222,6 +365,50 @@ pub fn create_tray_thread(core_sender: CoreSender) -> (JoinHandle<()>, TraySende
 
                         update_tray_app(&mut tray_app, &tray_state);
                     }
+                    TrayEvent::TogglePinnedProgram(app_index) => {
+                        let (app_path, _) = &tray_state.recent_apps[app_index];
+
+                        let pinned_app = match &tray_state.pinned_app {
+                            Some(pinned_app) if pinned_app == app_path => None,
+                            _ => Some(app_path.clone()),
+                        };
+
+                        core_sender
+                            .send(CoreMessage::SetPinnedApp(pinned_app))
+                            .map_err(|err| error!("Cannot send to core: {}", err))
+                            .ok();
+                    }
+                    TrayEvent::SetProgramExemption(app_index, exemption) => {
+                        let (app_path, _) = &tray_state.recent_apps[app_index];
+
+                        core_sender
+                            .send(CoreMessage::SetExemptApp(app_path.clone(), exemption))
+                            .map_err(|err| error!("Cannot send to core: {}", err))
+                            .ok();
+                    }
+                    TrayEvent::SetProgramCategory(app_index, category) => {
+                        let (app_path, _) = &tray_state.recent_apps[app_index];
+
+                        core_sender
+                            .send(CoreMessage::SetAppCategory(app_path.clone(), category))
+                            .map_err(|err| error!("Cannot send to core: {}", err))
+                            .ok();
+                    }
+                    TrayEvent::UpdateAppCategory(app_path, category) => {
+                        tray_state.app_categories.insert(app_path, category);
+
+                        update_tray_app(&mut tray_app, &tray_state);
+                    }
+                    TrayEvent::UpdateExemptApps(exempt_apps) => {
+                        tray_state.exempt_apps = exempt_apps;
+
+                        update_tray_app(&mut tray_app, &tray_state);
+                    }
+                    TrayEvent::UpdatePinnedApp(pinned_app) => {
+                        tray_state.pinned_app = pinned_app;
+
+                        update_tray_app(&mut tray_app, &tray_state);
+                    }
                     TrayEvent::AddRecentApp(app_path, app_active) => {
                         let recent = &mut tray_state.recent_apps;
 
@@ -258,6 +445,18 @@ pub fn create_tray_thread(core_sender: CoreSender) -> (JoinHandle<()>, TraySende
                             .map_err(|err| error!("Cannot send to core: {}", err))
                             .ok();
                     }
+                    TrayEvent::ShowStatus => {
+                        core_sender
+                            .send(CoreMessage::ShowStatus)
+                            .map_err(|err| error!("Cannot send to core: {}", err))
+                            .ok();
+                    }
+                    TrayEvent::ShowAudioSessions => {
+                        core_sender
+                            .send(CoreMessage::ShowAudioSessions)
+                            .map_err(|err| error!("Cannot send to core: {}", err))
+                            .ok();
+                    }
                     TrayEvent::ShowAbout => {
                         show_about_message();
                     }
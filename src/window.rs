@@ -1,6 +1,7 @@
 use std::{
     error::Error,
     fmt::{Debug, Display},
+    mem,
 };
 
 use comedy::Win32Error;
@@ -9,18 +10,33 @@ use unicase::UniCase;
 use winapi::{
     shared::{
         minwindef::{BOOL, DWORD, FALSE, LPARAM, TRUE},
-        windef::HWND,
+        windef::{HWND, RECT},
+        winerror::S_OK,
     },
     um::{
+        dwmapi::{DwmGetWindowAttribute, DWMWA_CLOAKED, DWMWA_EXTENDED_FRAME_BOUNDS},
         handleapi::CloseHandle,
         processthreadsapi::OpenProcess,
+        shellapi::{SHQueryUserNotificationState, QUNS_BUSY, QUNS_RUNNING_D3D_FULL_SCREEN},
         winbase::QueryFullProcessImageNameW,
+        wingdi::{
+            CombineRgn, CreateRectRgn, DeleteObject, ERROR, NULLREGION, RGN_AND, RGN_DIFF, RGN_OR,
+        },
         winnt::PROCESS_QUERY_INFORMATION,
-        winuser::{EnumWindows, GetWindow, GetWindowThreadProcessId, IsWindowVisible, GW_OWNER},
+        winuser::{
+            EnumWindows, GetClassNameW, GetForegroundWindow, GetMonitorInfoW, GetSystemMetrics,
+            GetWindow, GetWindowLongW, GetWindowRect, GetWindowThreadProcessId, IsIconic,
+            IsWindowVisible, MonitorFromWindow, GWL_EXSTYLE, GWL_STYLE, GW_OWNER, MONITORINFO,
+            MONITOR_DEFAULTTONULL, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN,
+            SM_YVIRTUALSCREEN, WS_CAPTION, WS_EX_TOPMOST, WS_EX_TRANSPARENT,
+        },
     },
 };
 
-use crate::core::ProgramPath;
+use crate::{
+    core::ProgramPath,
+    process::{self, ProcessId},
+};
 
 pub struct WindowError {
     hwnd: usize,
@@ -50,11 +66,27 @@ impl Debug for WindowError {
 
 impl Error for WindowError {}
 
+#[derive(Clone, Copy, Debug)]
+pub struct WindowRect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
 #[derive(Debug)]
 pub struct Window {
     pub hwnd: HWND,
-    pub pid: DWORD,
+    pub process: ProcessId,
     pub program_path: ProgramPath,
+    pub rect: WindowRect,
+    pub minimized: bool,
+    /// Hidden by the compositor, e.g. on another virtual desktop.
+    pub cloaked: bool,
+    /// Click-through, e.g. overlays.
+    pub transparent: bool,
+    /// Kept above other windows, e.g. picture-in-picture videos.
+    pub topmost: bool,
 }
 
 impl Window {
@@ -74,6 +106,9 @@ impl Window {
                 ))?;
             }
 
+            let start_time = process::get_process_start_time(pid)
+                .ok_or_else(|| WindowError::new(hwnd, "Could not retrieve process start time"))?;
+
             // check that window is visible
             if IsWindowVisible(hwnd) == FALSE {
                 Err(WindowError::new(hwnd, "Window is not visible"))?;
@@ -97,16 +132,113 @@ impl Window {
             let program_path = String::from_utf16(&path_buf[..(buf_size as _)])?;
             let program_path = UniCase::new(program_path.into());
 
+            // get geometry and state
+
+            let rect = Self::get_rect(hwnd);
+            let minimized = IsIconic(hwnd) != FALSE;
+
+            let mut cloaked: DWORD = 0;
+            DwmGetWindowAttribute(
+                hwnd,
+                DWMWA_CLOAKED,
+                &mut cloaked as *mut DWORD as _,
+                mem::size_of::<DWORD>() as _,
+            );
+
+            let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as DWORD;
+            let transparent = ex_style & WS_EX_TRANSPARENT != 0;
+            let topmost = ex_style & WS_EX_TOPMOST != 0;
+
             // ok
 
             Ok(Self {
                 hwnd,
-                pid,
+                process: ProcessId { pid, start_time },
                 program_path,
+                rect,
+                minimized,
+                cloaked: cloaked != 0,
+                transparent,
+                topmost,
             })
         }
     }
 
+    /// Whether the window covers its entire monitor, e.g. games or presentations. Only checked
+    /// for the foreground window, as it queries the shell and the monitor.
+    pub fn is_fullscreen(hwnd: HWND) -> bool {
+        unsafe {
+            if IsIconic(hwnd) != FALSE {
+                return false;
+            }
+
+            // the desktop covers the entire monitor as well
+            let mut class_buf = [0u16; 16];
+            let class_len = GetClassNameW(hwnd, class_buf.as_mut_ptr(), class_buf.len() as _);
+            let class_name = String::from_utf16_lossy(&class_buf[..(class_len as _)]);
+            if class_name == "Progman" || class_name == "WorkerW" {
+                return false;
+            }
+
+            // the shell knows about exclusive fullscreen games and presentations, but only for
+            // the foreground window
+            if hwnd == GetForegroundWindow() {
+                let mut state = 0;
+                if SHQueryUserNotificationState(&mut state) == S_OK
+                    && (state == QUNS_BUSY || state == QUNS_RUNNING_D3D_FULL_SCREEN)
+                {
+                    return true;
+                }
+            }
+
+            // maximized windows cover the entire monitor as well while the taskbar auto-hides,
+            // but keep their title bar
+            if GetWindowLongW(hwnd, GWL_STYLE) as DWORD & WS_CAPTION == WS_CAPTION {
+                return false;
+            }
+
+            let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONULL);
+            if monitor.is_null() {
+                return false;
+            }
+
+            let mut monitor_info: MONITORINFO = mem::zeroed();
+            monitor_info.cbSize = mem::size_of::<MONITORINFO>() as _;
+            if GetMonitorInfoW(monitor, &mut monitor_info) == FALSE {
+                return false;
+            }
+
+            let window_rect = Self::get_rect(hwnd);
+            let monitor_rect = monitor_info.rcMonitor;
+
+            window_rect.left <= monitor_rect.left
+                && window_rect.top <= monitor_rect.top
+                && window_rect.right >= monitor_rect.right
+                && window_rect.bottom >= monitor_rect.bottom
+        }
+    }
+
+    unsafe fn get_rect(hwnd: HWND) -> WindowRect {
+        // the extended frame bounds exclude the invisible resize borders
+        let mut rect: RECT = mem::zeroed();
+        let hresult = DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut rect as *mut RECT as _,
+            mem::size_of::<RECT>() as _,
+        );
+        if hresult != S_OK {
+            GetWindowRect(hwnd, &mut rect);
+        }
+
+        WindowRect {
+            left: rect.left,
+            top: rect.top,
+            right: rect.right,
+            bottom: rect.bottom,
+        }
+    }
+
     pub fn all_windows() -> Vec<Window> {
         unsafe extern "system" fn enumerate_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
             if let Ok(window) = Window::new_from_hwnd(hwnd) {
@@ -126,4 +258,73 @@ impl Window {
             windows
         }
     }
+
+    /// Whether a process has a visible top-level window.
+    pub fn process_has_window(pid: DWORD) -> bool {
+        unsafe extern "system" fn enumerate_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
+            let search = lparam as *mut (DWORD, bool);
+
+            let mut pid: DWORD = 0;
+            GetWindowThreadProcessId(hwnd, &mut pid);
+            if pid == (*search).0 && IsWindowVisible(hwnd) != FALSE {
+                (*search).1 = true;
+                return FALSE;
+            }
+
+            TRUE
+        }
+
+        unsafe {
+            let mut search = (pid, false);
+            EnumWindows(
+                Some(enumerate_callback),
+                &mut search as *mut (DWORD, bool) as _,
+            );
+            search.1
+        }
+    }
+
+    /// Windows that are not minimized and not fully covered by other windows or off-screen.
+    pub fn unoccluded_windows() -> Vec<Window> {
+        unsafe {
+            let screen = CreateRectRgn(
+                GetSystemMetrics(SM_XVIRTUALSCREEN),
+                GetSystemMetrics(SM_YVIRTUALSCREEN),
+                GetSystemMetrics(SM_XVIRTUALSCREEN) + GetSystemMetrics(SM_CXVIRTUALSCREEN),
+                GetSystemMetrics(SM_YVIRTUALSCREEN) + GetSystemMetrics(SM_CYVIRTUALSCREEN),
+            );
+            let covered = CreateRectRgn(0, 0, 0, 0);
+            let uncovered = CreateRectRgn(0, 0, 0, 0);
+
+            let mut windows = vec![];
+
+            // windows are enumerated in z-order, topmost first
+            for window in Self::all_windows() {
+                if window.minimized || window.cloaked {
+                    continue;
+                }
+
+                let rect = window.rect;
+                let window_region = CreateRectRgn(rect.left, rect.top, rect.right, rect.bottom);
+
+                CombineRgn(uncovered, window_region, screen, RGN_AND);
+                let region_kind = CombineRgn(uncovered, uncovered, covered, RGN_DIFF);
+
+                if !window.transparent {
+                    CombineRgn(covered, covered, window_region, RGN_OR);
+                }
+                DeleteObject(window_region as _);
+
+                if region_kind != NULLREGION && region_kind != ERROR {
+                    windows.push(window);
+                }
+            }
+
+            DeleteObject(screen as _);
+            DeleteObject(covered as _);
+            DeleteObject(uncovered as _);
+
+            windows
+        }
+    }
 }
@@ -1,6 +1,9 @@
 use std::{
     os::windows::prelude::AsRawHandle,
-    sync::Mutex,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
     thread::{self, JoinHandle},
 };
 
@@ -9,7 +12,7 @@ use log::info;
 use once_cell::sync::Lazy;
 use winapi::{
     shared::{
-        minwindef::{DWORD, UINT},
+        minwindef::{DWORD, FALSE, UINT},
         ntdef::LONG,
         windef::{HWINEVENTHOOK, HWND},
     },
@@ -17,38 +20,175 @@ use winapi::{
         combaseapi::{CoInitializeEx, CoUninitialize},
         processthreadsapi::GetThreadId,
         winuser::{
-            DispatchMessageW, GetMessageW, PostQuitMessage, PostThreadMessageW, SetWinEventHook,
-            TranslateMessage, UnhookWinEvent, EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_MINIMIZEEND,
-            WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS, WM_USER,
+            DispatchMessageW, GetForegroundWindow, GetMessageW, GetWindowThreadProcessId, IsIconic,
+            KillTimer, PostQuitMessage, PostThreadMessageW, SetTimer, SetWinEventHook,
+            TranslateMessage, UnhookWinEvent, EVENT_OBJECT_CLOAKED, EVENT_OBJECT_LOCATIONCHANGE,
+            EVENT_OBJECT_UNCLOAKED, EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_MINIMIZEEND,
+            EVENT_SYSTEM_MINIMIZESTART, EVENT_SYSTEM_MOVESIZEEND, OBJID_WINDOW,
+            WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS, WM_TIMER, WM_USER,
         },
     },
 };
 
-use crate::core::{CoreMessage, CoreSender};
+use crate::{
+    core::{CoreMessage, CoreSender},
+    window::Window,
+};
 
-const TARGET_EVENTS: &[DWORD] = &[EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_MINIMIZEEND];
+const TARGET_EVENTS: &[DWORD] = &[
+    EVENT_SYSTEM_FOREGROUND,
+    EVENT_SYSTEM_MINIMIZEEND,
+    EVENT_SYSTEM_MINIMIZESTART,
+    EVENT_SYSTEM_MOVESIZEEND,
+    EVENT_OBJECT_CLOAKED,
+    EVENT_OBJECT_UNCLOAKED,
+];
 const TARGET_DW_FLAGS: UINT = WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS;
 const WM_STOP_LISTENING: UINT = WM_USER + 1;
 
+// switching virtual desktops (un)cloaks every window on them -> report once after they settle
+const WORKSPACE_CHANGE_DELAY_MS: UINT = 200;
+
+// moving or resizing a window fires location changes continuously -> check once they settle
+const FULLSCREEN_CHECK_DELAY_MS: UINT = 100;
+
+// windows only count as minimized once the minimize animation is over -> report after it
+const MINIMIZE_DELAY_MS: UINT = 300;
+
 // WinEventProc doesn't accept custom data -> communicate via a static
 static CORE_SENDER: Lazy<Mutex<Option<CoreSender>>> = Lazy::new(|| Mutex::new(None));
+static WORKSPACE_TIMER_ID: AtomicUsize = AtomicUsize::new(0);
+static FULLSCREEN_TIMER_ID: AtomicUsize = AtomicUsize::new(0);
+static MINIMIZE_TIMER_ID: AtomicUsize = AtomicUsize::new(0);
+static MINIMIZING_WINDOWS: Lazy<Mutex<Vec<usize>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static FOREGROUND_FULLSCREEN: AtomicBool = AtomicBool::new(false);
+// location changes are only hooked for the foreground process, not system-wide
+static LOCATION_HOOK: AtomicUsize = AtomicUsize::new(0);
+
+fn send_to_core(message: CoreMessage) {
+    let Some(lock) = CORE_SENDER.lock().ok() else { return };
+    if let Some(core_sender) = &*lock {
+        core_sender.send(message).ok();
+    }
+}
+
+/// (Re)starts a thread timer, so that it only fires once a burst of events is over.
+unsafe fn schedule_timer(timer_id: &AtomicUsize, delay_ms: UINT) {
+    let old_timer_id = timer_id.swap(0, Ordering::SeqCst);
+    if old_timer_id != 0 {
+        KillTimer(0 as _, old_timer_id);
+    }
+
+    timer_id.store(SetTimer(0 as _, 0, delay_ms, None), Ordering::SeqCst);
+}
+
+/// Handles an expired thread timer. Returns whether it was `timer_id`.
+unsafe fn take_timer(timer_id: &AtomicUsize, expired_timer_id: usize) -> bool {
+    if expired_timer_id == 0 || timer_id.load(Ordering::SeqCst) != expired_timer_id {
+        return false;
+    }
+
+    KillTimer(0 as _, expired_timer_id);
+    timer_id.store(0, Ordering::SeqCst);
+    true
+}
+
+fn check_foreground_fullscreen() {
+    let hwnd = unsafe { GetForegroundWindow() };
+    if hwnd.is_null() {
+        return;
+    }
+
+    let fullscreen = Window::is_fullscreen(hwnd);
+    if FOREGROUND_FULLSCREEN.swap(fullscreen, Ordering::SeqCst) != fullscreen {
+        send_to_core(CoreMessage::FullscreenChanged(hwnd as usize, fullscreen));
+    }
+}
+
+/// Reports the windows whose minimize animation has finished. Windows that were restored in the
+/// meantime are skipped.
+fn report_minimized_windows() {
+    let Some(mut lock) = MINIMIZING_WINDOWS.lock().ok() else { return };
+    for hwnd in lock.drain(..) {
+        if unsafe { IsIconic(hwnd as HWND) } != FALSE {
+            send_to_core(CoreMessage::WindowMinimized(hwnd));
+        }
+    }
+}
+
+unsafe fn unhook_location_changes() {
+    let old_hook = LOCATION_HOOK.swap(0, Ordering::SeqCst);
+    if old_hook != 0 {
+        UnhookWinEvent(old_hook as HWINEVENTHOOK);
+    }
+}
 
 unsafe fn window_listener_loop() {
     unsafe extern "system" fn window_change_callback(
         _: HWINEVENTHOOK,
-        _: DWORD,
+        event: DWORD,
         hwnd: HWND,
-        _: LONG,
+        id_object: LONG,
         _: LONG,
         _: DWORD,
         _: DWORD,
     ) {
-        let Some(lock) = CORE_SENDER.lock().ok() else { return };
-        if let Some(core_sender) = &*lock {
-            core_sender
-                .send(CoreMessage::NewForegroundWindow(hwnd as usize))
-                .ok();
+        let message = match event {
+            EVENT_OBJECT_CLOAKED | EVENT_OBJECT_UNCLOAKED => {
+                if id_object == OBJID_WINDOW {
+                    schedule_timer(&WORKSPACE_TIMER_ID, WORKSPACE_CHANGE_DELAY_MS);
+                }
+                return;
+            }
+            EVENT_OBJECT_LOCATIONCHANGE => {
+                // only report when the foreground window enters or leaves fullscreen
+                if id_object == OBJID_WINDOW && hwnd == GetForegroundWindow() {
+                    schedule_timer(&FULLSCREEN_TIMER_ID, FULLSCREEN_CHECK_DELAY_MS);
+                }
+                return;
+            }
+            EVENT_SYSTEM_FOREGROUND => {
+                hook_location_changes(hwnd);
+                FOREGROUND_FULLSCREEN.store(Window::is_fullscreen(hwnd), Ordering::SeqCst);
+                CoreMessage::NewForegroundWindow(hwnd as usize)
+            }
+            EVENT_SYSTEM_MINIMIZESTART => {
+                if let Ok(mut lock) = MINIMIZING_WINDOWS.lock() {
+                    if !lock.contains(&(hwnd as usize)) {
+                        lock.push(hwnd as usize);
+                    }
+                }
+                schedule_timer(&MINIMIZE_TIMER_ID, MINIMIZE_DELAY_MS);
+                return;
+            }
+            EVENT_SYSTEM_MINIMIZEEND => CoreMessage::WindowRestored(hwnd as usize),
+            EVENT_SYSTEM_MOVESIZEEND => CoreMessage::WindowLayoutChanged,
+            _ => CoreMessage::NewForegroundWindow(hwnd as usize),
+        };
+
+        send_to_core(message);
+    }
+
+    // moves the location hook over to the process of the new foreground window
+    unsafe fn hook_location_changes(hwnd: HWND) {
+        unhook_location_changes();
+
+        let mut pid: DWORD = 0;
+        GetWindowThreadProcessId(hwnd, &mut pid);
+        if pid == 0 {
+            return;
         }
+
+        let hook = SetWinEventHook(
+            EVENT_OBJECT_LOCATIONCHANGE,
+            EVENT_OBJECT_LOCATIONCHANGE,
+            0 as _,
+            Some(window_change_callback),
+            pid,
+            0,
+            TARGET_DW_FLAGS,
+        );
+        LOCATION_HOOK.store(hook as usize, Ordering::SeqCst);
     }
 
     info!("Listener start");
@@ -78,6 +218,8 @@ unsafe fn window_listener_loop() {
         })
         .collect_vec();
 
+    hook_location_changes(GetForegroundWindow());
+
     // run message loop
 
     let mut msg = std::mem::zeroed();
@@ -87,6 +229,15 @@ unsafe fn window_listener_loop() {
             WM_STOP_LISTENING => {
                 PostQuitMessage(0);
             }
+            WM_TIMER if msg.hwnd.is_null() && take_timer(&WORKSPACE_TIMER_ID, msg.wParam) => {
+                send_to_core(CoreMessage::WorkspaceChanged);
+            }
+            WM_TIMER if msg.hwnd.is_null() && take_timer(&FULLSCREEN_TIMER_ID, msg.wParam) => {
+                check_foreground_fullscreen();
+            }
+            WM_TIMER if msg.hwnd.is_null() && take_timer(&MINIMIZE_TIMER_ID, msg.wParam) => {
+                report_minimized_windows();
+            }
             _ => {
                 TranslateMessage(&msg);
                 DispatchMessageW(&msg);
@@ -99,6 +250,7 @@ unsafe fn window_listener_loop() {
     for hook in hooks {
         UnhookWinEvent(hook);
     }
+    unhook_location_changes();
 
     CoUninitialize();
 